@@ -0,0 +1,134 @@
+use std::f64::consts::PI;
+
+const PHASE_BITS: u32 = 5;
+const PHASES: usize = 1 << PHASE_BITS; //sub sample positions of a step
+const TAPS: usize = 16; //width of a step in output samples
+const FRACTION_BITS: u32 = 32;
+const CUTOFF: f64 = 0.9; //of the nyquist frequency
+
+/*
+  band limited synthesis - every change of the amplitude is added as a step at the exact tick it happened.
+  the steps are low pass filtered impulses spread over a few output samples so square waves don't alias
+  and pulses shorter than a sample still come through. reading the samples sums the impulses up again.
+*/
+pub struct BlipBuffer {
+  samples_per_tick: u64, //fixed point with FRACTION_BITS
+  offset: u64, //position of tick 0 in the buffer - fixed point
+  buffer: Vec<f32>,
+  sum: f32,
+  kernel: [[f32; TAPS]; PHASES]
+}
+
+impl BlipBuffer {
+  //max_ticks is the most ticks between two calls of read_samples
+  pub fn new(clock_rate: usize, sample_rate: usize, max_ticks: usize) -> BlipBuffer {
+    let samples_per_tick = ((sample_rate as u64) << FRACTION_BITS) / clock_rate as u64;
+    let max_samples = ((max_ticks as u64 * samples_per_tick) >> FRACTION_BITS) as usize + 1;
+
+    BlipBuffer {
+      samples_per_tick,
+      offset: 0,
+      buffer: vec![0.0; max_samples + TAPS],
+      sum: 0.0,
+      kernel: BlipBuffer::kernel()
+    }
+  }
+
+  //windowed sinc impulses for every phase - each sums up to 1 so a step reaches exactly its height
+  fn kernel() -> [[f32; TAPS]; PHASES] {
+    let mut kernel = [[0.0; TAPS]; PHASES];
+    for (phase, impulse) in kernel.iter_mut().enumerate() {
+      let fraction = phase as f64 / PHASES as f64;
+      let mut total = 0.0;
+      for (tap, value) in impulse.iter_mut().enumerate() {
+        let x = tap as f64 - fraction - (TAPS / 2) as f64 + 1.0;
+        let sinc = if x == 0.0 { 1.0 } else { (PI * CUTOFF * x).sin() / (PI * CUTOFF * x) };
+        let window = 0.42 + 0.5 * (PI * x / (TAPS / 2) as f64).cos() + 0.08 * (2.0 * PI * x / (TAPS / 2) as f64).cos(); //blackman
+        let window = if x.abs() >= (TAPS / 2) as f64 { 0.0 } else { window };
+        *value = (sinc * window) as f32;
+        total += *value as f64;
+      }
+      for value in impulse.iter_mut() {
+        *value /= total as f32;
+      }
+    }
+    kernel
+  }
+
+  //the amplitude changed by delta at the given tick since the last read
+  pub fn add_delta(&mut self, tick: usize, delta: f32) {
+    let position = self.offset + tick as u64 * self.samples_per_tick;
+    let index = (position >> FRACTION_BITS) as usize;
+    let phase = ((position >> (FRACTION_BITS - PHASE_BITS)) & (PHASES as u64 - 1)) as usize;
+
+    if let Some(target) = self.buffer.get_mut(index .. index + TAPS) {
+      for (sample, value) in target.iter_mut().zip(self.kernel[phase].iter()) {
+        *sample += delta * value;
+      }
+    }
+  }
+
+  //moves the given ticks forward and appends the finished samples - a step is only finished TAPS samples later
+  pub fn read_samples(&mut self, ticks: usize, output: &mut Vec<f32>) {
+    let end = self.offset + ticks as u64 * self.samples_per_tick;
+    let count = ((end >> FRACTION_BITS) as usize).min(self.buffer.len() - TAPS);
+
+    for sample in self.buffer[.. count].iter() {
+      self.sum += sample;
+      output.push(self.sum);
+    }
+
+    self.buffer.copy_within(count .., 0);
+    let length = self.buffer.len();
+    self.buffer[length - count ..].fill(0.0);
+    self.offset = end - ((count as u64) << FRACTION_BITS);
+  }
+
+  pub fn clear(&mut self) {
+    self.buffer.fill(0.0);
+    self.offset = 0;
+    self.sum = 0.0;
+  }
+}
+
+/*
+  the output capacitor of the game boy removes the DC offset of the DACs - without it turning a channel on or off pops.
+  it charges slower on the DMG than on the later models.
+*/
+pub struct HighPass {
+  charge: f32,
+  capacitor: f32
+}
+
+impl HighPass {
+  pub fn new(charge_factor: f64, clock_rate: usize, sample_rate: usize) -> HighPass {
+    HighPass {
+      charge: charge_factor.powf(clock_rate as f64 / sample_rate as f64) as f32,
+      capacitor: 0.0
+    }
+  }
+
+  pub fn filter(&mut self, input: f32) -> f32 {
+    let output = input - self.capacitor;
+    self.capacitor = input - output * self.charge;
+    output
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn a_step_settles_at_its_height()
+  {
+    let mut blip = BlipBuffer::new(4_194_304, 48_000, 4096);
+    blip.add_delta(100, 10.0);
+    let mut samples = vec![];
+    blip.read_samples(4096, &mut samples);
+
+    assert!(samples[0].abs() < 0.01);
+    assert!((samples.last().unwrap() - 10.0).abs() < 0.001);
+  }
+}
@@ -1,6 +1,7 @@
 mod tone;
 mod noise;
 mod wave;
+mod blip;
 
 use crate::CPU_FREQUENCY;
 use crate::AUDIO_OUTPUT_FREQUENCY;
@@ -8,20 +9,37 @@ use crate::AUDIO_BUFFER_SIZE;
 use crate::apu::wave::Wave;
 use crate::apu::tone::Tone;
 use crate::apu::noise::Noise;
-
-use std::sync::mpsc::Sender;
-use std::thread::park;
-
-const SAMPLE_TICKS: usize = CPU_FREQUENCY / AUDIO_OUTPUT_FREQUENCY;
-const TIMER_TICKS: usize = CPU_FREQUENCY / 512; //timer clock is at 512hz
+use crate::apu::blip::{BlipBuffer, HighPass};
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+use crate::model::HardwareModel;
+
+const STEP_TICKS: usize = 2; //every timer of the channels runs in multiples of 2 ticks - the changes are recorded at the exact tick
+const READ_TICKS: usize = 1024; //the samples are read from the blip buffers every 1024 ticks - about 11 samples
+
+//the bits that read as 1 for FF10 - FF2F - write only and unused bits
+const READ_MASKS: [u8; 0x20] = [
+  0x80, 0x3F, 0x00, 0xFF, 0xBF, //NR10 - NR14
+  0xFF, 0x3F, 0x00, 0xFF, 0xBF, //unused, NR21 - NR24
+  0x7F, 0xFF, 0x9F, 0xFF, 0xBF, //NR30 - NR34
+  0xFF, 0xFF, 0x00, 0x00, 0xBF, //unused, NR41 - NR44
+  0x00, 0x00, 0x70, //NR50 - NR52
+  0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF //unused
+];
 
 pub struct Apu {
   enabled: bool,
-  audio_sender: Sender<Vec<i16>>,
-  counter: usize,
-  buffer: Vec<i16>,
-  timer_counter: usize,
-  timer_step: usize,
+  length_writable_while_off: bool, //the DMG keeps the length counters powered
+  buffer: Vec<i16>, //interleaved stereo samples generated since the buffer was last cleared
+  charge_factor: f64, //of the output capacitor
+  clock: usize, //ticks since the samples were last read from the blip buffers
+  amplitude: (i16, i16), //the last output of the mixer
+  blip_left: BlipBuffer,
+  blip_right: BlipBuffer,
+  high_pass_left: HighPass,
+  high_pass_right: HighPass,
+  samples_left: Vec<f32>,
+  samples_right: Vec<f32>,
+  timer_step: usize, //the last step of the 512hz frame sequencer
 
   channel_1: Tone,
   channel_2: Tone,
@@ -32,13 +50,25 @@ pub struct Apu {
 }
 
 impl Apu {
-  pub fn new(audio_sender: Sender<Vec<i16>>) -> Apu {
+  pub fn new(model: HardwareModel) -> Apu {
+    let charge_factor = match model { //the capacitor of the DMG charges slower
+      HardwareModel::DMG0 | HardwareModel::DMG | HardwareModel::SGB => 0.999958,
+      _ => 0.998943
+    };
+
     Apu {
       enabled: true,
-      audio_sender,
-      counter: 0,
-      buffer: vec![],
-      timer_counter: 0,
+      length_writable_while_off: !model.is_cgb(),
+      buffer: Vec::with_capacity(AUDIO_BUFFER_SIZE),
+      charge_factor,
+      clock: 0,
+      amplitude: (0, 0),
+      blip_left: BlipBuffer::new(CPU_FREQUENCY, AUDIO_OUTPUT_FREQUENCY, READ_TICKS + STEP_TICKS),
+      blip_right: BlipBuffer::new(CPU_FREQUENCY, AUDIO_OUTPUT_FREQUENCY, READ_TICKS + STEP_TICKS),
+      high_pass_left: HighPass::new(charge_factor, CPU_FREQUENCY, AUDIO_OUTPUT_FREQUENCY),
+      high_pass_right: HighPass::new(charge_factor, CPU_FREQUENCY, AUDIO_OUTPUT_FREQUENCY),
+      samples_left: Vec::new(),
+      samples_right: Vec::new(),
       timer_step: 0,
       channel_1: Tone::new(),
       channel_2: Tone::new(),
@@ -49,7 +79,7 @@ impl Apu {
   }
 
   pub fn read_byte(&self, address: u16) -> u8 {
-    match address {
+    let value = match address {
       0xFF10 ..= 0xFF14 => self.channel_1.read_byte(address),
       0xFF16 ..= 0xFF19 => self.channel_2.read_byte(address),
       0xFF1A ..= 0xFF1E => self.channel_3.read_byte(address),
@@ -64,40 +94,81 @@ impl Apu {
         if self.channel_1.is_enabled() { ret |= 0b0000_0001; }
         ret
       },
-      0xFF30 ..= 0xFF3F => self.channel_3.read_byte(address),
+      0xFF30 ..= 0xFF3F => return self.channel_3.read_byte(address),
       _ => 0
+    };
+
+    match address {
+      0xFF10 ..= 0xFF2F => value | READ_MASKS[address as usize - 0xFF10],
+      _ => 0xFF
     }
   }
 
+  //while the apu is off only NR52 and the wave ram can be written - and the lengths on the DMG
   pub fn write_byte(&mut self, address: u16, value: u8) {
+    if !self.enabled {
+      match address {
+        0xFF26 | 0xFF30 ..= 0xFF3F => (),
+        0xFF11 if self.length_writable_while_off => { self.channel_1.load_length(value); return },
+        0xFF16 if self.length_writable_while_off => { self.channel_2.load_length(value); return },
+        0xFF1B if self.length_writable_while_off => { self.channel_3.load_length(value); return },
+        0xFF20 if self.length_writable_while_off => { self.channel_4.load_length(value); return },
+        _ => return
+      }
+    }
+
+    let extra_length_clock = self.timer_step.is_multiple_of(2); //the last step clocked the length counters - the next one won't
     match address {
-      0xFF10 ..= 0xFF14 => self.channel_1.write_byte(address, value),
-      0xFF16 ..= 0xFF19 => self.channel_2.write_byte(address, value),
-      0xFF1A ..= 0xFF1E => self.channel_3.write_byte(address, value),
-      0xFF20 ..= 0xFF23 => self.channel_4.write_byte(address, value),
+      0xFF10 ..= 0xFF14 => self.channel_1.write_byte(address, value, extra_length_clock),
+      0xFF16 ..= 0xFF19 => self.channel_2.write_byte(address, value, extra_length_clock),
+      0xFF1A ..= 0xFF1E => self.channel_3.write_byte(address, value, extra_length_clock),
+      0xFF20 ..= 0xFF23 => self.channel_4.write_byte(address, value, extra_length_clock),
       0xFF24 ..= 0xFF25 => self.mixer.write_byte(address, value),
       0xFF26 => self.set_enabled(value & 0b1000_0000 == 0b1000_0000),
-      0xFF30 ..= 0xFF3F => self.channel_3.write_byte(address, value),
+      0xFF30 ..= 0xFF3F => self.channel_3.write_byte(address, value, extra_length_clock),
       _ => ()
     }
   }
 
+  //channel 1 played the boot sound - NR52 shows it as still on
+  pub fn finish_boot_sound(&mut self) {
+    self.channel_1.finish_boot_sound();
+  }
+
+  pub fn get_audio_buffer(&self) -> &[i16] {
+    &self.buffer
+  }
+
+  pub fn clear_audio_buffer(&mut self) {
+    self.buffer.clear();
+  }
+
+  //powering off clears all registers - powering on restarts the frame sequencer so its next step clocks the lengths
   fn set_enabled(&mut self, play: bool) {
+    if self.enabled && !play {
+      let keep_length = self.length_writable_while_off;
+      self.channel_1.power_off(keep_length);
+      self.channel_2.power_off(keep_length);
+      self.channel_3.power_off(keep_length);
+      self.channel_4.power_off(keep_length);
+      self.mixer = Mixer::new();
+    } else if !self.enabled && play {
+      self.timer_step = 7;
+    }
     self.enabled = play;
   }
 
   pub fn do_ticks(&mut self, ticks: usize) {
-    self.do_timer(ticks);
+    let mut ticks = ticks;
+    while ticks > 0 {
+      let step = ticks.min(STEP_TICKS);
+      ticks -= step;
 
-    self.counter += ticks;
-
-    self.channel_1.do_ticks(ticks);
-    self.channel_2.do_ticks(ticks);
-    self.channel_3.do_ticks(ticks);
-    self.channel_4.do_ticks(ticks);
-
-    while self.counter >= SAMPLE_TICKS {
-      self.counter -= SAMPLE_TICKS;
+      self.channel_1.do_ticks(step);
+      self.channel_2.do_ticks(step);
+      self.channel_3.do_ticks(step);
+      self.channel_4.do_ticks(step);
+      self.clock += step;
 
       let ch1 = self.channel_1.get_sample();
       let ch2 = self.channel_2.get_sample();
@@ -105,44 +176,73 @@ impl Apu {
       let ch4 = self.channel_4.get_sample();
 
       let (left, right) = self.mixer.mix(ch1, ch2, ch3, ch4);
+      if left != self.amplitude.0 {
+        self.blip_left.add_delta(self.clock, (left - self.amplitude.0) as f32);
+      }
+      if right != self.amplitude.1 {
+        self.blip_right.add_delta(self.clock, (right - self.amplitude.1) as f32);
+      }
+      self.amplitude = (left, right);
 
-      self.buffer.push(left);
-      self.buffer.push(right);
-
-      if self.buffer.len() >= AUDIO_BUFFER_SIZE {
-        self.audio_sender.send(self.buffer.clone()).expect("Failed to send audio buffer");
-        self.buffer.clear();
-        park(); //wait until the main thread tells us to continue
+      if self.clock >= READ_TICKS {
+        self.read_samples();
       }
     }
   }
 
-  fn do_timer(&mut self, ticks: usize) {
-    self.timer_counter += ticks;
+  //the samples that were not read yet are dropped
+  pub fn set_sample_rate(&mut self, sample_rate: usize) {
+    self.blip_left = BlipBuffer::new(CPU_FREQUENCY, sample_rate, READ_TICKS + STEP_TICKS);
+    self.blip_right = BlipBuffer::new(CPU_FREQUENCY, sample_rate, READ_TICKS + STEP_TICKS);
+    self.high_pass_left = HighPass::new(self.charge_factor, CPU_FREQUENCY, sample_rate);
+    self.high_pass_right = HighPass::new(self.charge_factor, CPU_FREQUENCY, sample_rate);
+    self.clock = 0;
+    self.amplitude = (0, 0);
+    self.buffer.clear();
+  }
+
+  fn read_samples(&mut self) {
+    self.samples_left.clear();
+    self.samples_right.clear();
+    self.blip_left.read_samples(self.clock, &mut self.samples_left);
+    self.blip_right.read_samples(self.clock, &mut self.samples_right);
+    self.clock = 0;
 
-    while self.timer_counter >= TIMER_TICKS {
-      self.timer_counter -= TIMER_TICKS;
+    for (left, right) in self.samples_left.iter().zip(self.samples_right.iter()) {
+      self.buffer.push(self.high_pass_left.filter(*left) as i16);
+      self.buffer.push(self.high_pass_right.filter(*right) as i16);
+    }
+  }
 
-      self.timer_step = ( self.timer_step + 1) % 8;
+  /*
+    the frame sequencer is clocked by the falling edge of bit 4 of DIV (bit 5 in double speed) - resetting DIV can clock it early
+      steps 0, 2, 4, 6 clock the length counters at 256hz
+      steps 2 and 6 clock the sweep at 128hz
+      step 7 clocks the volume envelopes at 64hz
+  */
+  pub fn step_frame_sequencer(&mut self) {
+    if !self.enabled {
+      return
+    }
 
-      if self.timer_step % 2 == 0 {
-        self.channel_1.timer_step();
-        self.channel_2.timer_step();
-        self.channel_3.timer_step();
-        self.channel_4.timer_step();
-      }
+    self.timer_step = (self.timer_step + 1) % 8;
 
-      if self.timer_step == 2 || self.timer_step == 6 {
-        self.channel_1.sweep_step();
-      }
+    if self.timer_step.is_multiple_of(2) {
+      self.channel_1.timer_step();
+      self.channel_2.timer_step();
+      self.channel_3.timer_step();
+      self.channel_4.timer_step();
+    }
 
-      if self.timer_step == 7 {
-        self.channel_1.envelope_step();
-        self.channel_2.envelope_step();
-        self.channel_4.envelope_step();
-      }
+    if self.timer_step == 2 || self.timer_step == 6 {
+      self.channel_1.sweep_step();
     }
 
+    if self.timer_step == 7 {
+      self.channel_1.envelope_step();
+      self.channel_2.envelope_step();
+      self.channel_4.envelope_step();
+    }
   }
 }
 
@@ -152,6 +252,7 @@ struct VolumeEnvelope {
   counter: usize,
   period: usize,
   increase: bool,
+  running: bool //stops when the volume reaches 0 or 15
 }
 
 impl VolumeEnvelope {
@@ -161,7 +262,8 @@ impl VolumeEnvelope {
       initial_volume: 0,
       counter: 0,
       period: 0,
-      increase: false
+      increase: false,
+      running: false
     }
   }
 
@@ -169,39 +271,117 @@ impl VolumeEnvelope {
     (self.initial_volume as u8) << 4 | if self.increase { 0b0000_1000 } else { 0 } | self.period as u8
   }
 
-  pub fn write_byte(&mut self, value: u8) {
+  /*
+    writing NRx2 while the channel plays changes the volume in "zombie mode" - some games use it to set the volume without restarting the note.
+    the volume goes up by one if the envelope is still running with a period of 0 or by two if it was decreasing.
+    changing the direction inverts it - the result wraps at 16.
+  */
+  pub fn write_byte(&mut self, value: u8, playing: bool) {
+    if playing {
+      let increase = value & 0b0000_1000 == 0b0000_1000;
+      if self.period == 0 && self.running {
+        self.volume += 1;
+      } else if !self.increase {
+        self.volume += 2;
+      }
+      if increase != self.increase {
+        self.volume = 16 - self.volume;
+      }
+      self.volume &= 0x0F;
+    }
+
     self.initial_volume = ((value & 0xF0) >> 4) as i16;
     self.increase = value & 0b0000_1000 == 0b0000_1000;
     self.period = (value & 0b0000_0111) as usize;
   }
 
+  //the upper 5 bits of NRx2 power the DAC of the channel
+  pub fn dac_enabled(&self) -> bool {
+    self.initial_volume > 0 || self.increase
+  }
+
   pub fn get_volume(&self) -> i16 {
     self.volume
   }
 
   pub fn reset(&mut self) {
-    self.counter = 0;
+    self.counter = if self.period > 0 { self.period } else { 8 }; //a period of 0 counts as 8 without changing the volume
     self.volume = self.initial_volume;
+    self.running = true;
   }
 
   pub fn step(&mut self) {
-    if self.period > 0 {
-      self.counter += 1;
+    if self.counter > 0 {
+      self.counter -= 1;
+    }
 
-      if self.counter >= self.period {
-        self.counter = 0;
+    if self.counter == 0 {
+      self.counter = if self.period > 0 { self.period } else { 8 };
 
+      if self.period > 0 && self.running {
         if self.increase && self.volume < 15 {
           self.volume += 1;
         } else if !self.increase && self.volume > 0 {
           self.volume -= 1;
+        } else {
+          self.running = false;
         }
       }
     }
   }
 }
 
+/*
+  the length counter turns the channel off when it runs out - 64 steps for tone and noise and 256 for the wave channel.
+  the frame sequencer clocks it at 256hz while it is enabled in NRx4.
+  enabling it when the next frame sequencer step doesn't clock the length clocks it once more - triggering with a length of 0 loads the maximum.
+*/
+struct LengthCounter {
+  enabled: bool,
+  counter: u16,
+  max: u16
+}
+
+impl LengthCounter {
+  pub fn new(max: u16) -> LengthCounter {
+    LengthCounter {
+      enabled: false,
+      counter: 0,
+      max
+    }
+  }
+
+  pub fn load(&mut self, value: u8) {
+    self.counter = self.max - value as u16;
+  }
+
+  //false if the counter ran out and the channel is turned off
+  pub fn step(&mut self) -> bool {
+    if self.enabled && self.counter > 0 {
+      self.counter -= 1;
+      return self.counter > 0
+    }
+    true
+  }
+
+  //the length enable and trigger bits of NRx4 - false if the extra clock turns the channel off
+  pub fn write_control(&mut self, enabled: bool, trigger: bool, extra_clock: bool) -> bool {
+    let mut keep_playing = true;
+    if !self.enabled && enabled && extra_clock && self.counter > 0 {
+      self.counter -= 1;
+      keep_playing = self.counter > 0 || trigger;
+    }
+    self.enabled = enabled;
+
+    if trigger && self.counter == 0 {
+      self.counter = if enabled && extra_clock { self.max - 1 } else { self.max };
+    }
+    keep_playing
+  }
+}
+
 struct Mixer {
+  vin: u8, //NR50 bits 7 and 3 - the cartridge audio input is not emulated
   vol_left: i16,
   vol_right: i16,
 
@@ -218,6 +398,7 @@ struct Mixer {
 impl Mixer {
   pub fn new() -> Mixer {
     Mixer {
+      vin: 0,
       vol_left: 0,
       vol_right: 0,
 
@@ -234,7 +415,7 @@ impl Mixer {
 
   pub fn read_byte(&self, address: u16) -> u8 {
     match address {
-      0xFF24 => (self.vol_left << 4 | self.vol_right) as u8,
+      0xFF24 => self.vin | (self.vol_left << 4 | self.vol_right) as u8,
       0xFF25 => {
         let mut val = 0u8;
         if self.ch4_l { val |= 0b1000_0000 };
@@ -253,7 +434,8 @@ impl Mixer {
 
   pub fn write_byte(&mut self, address: u16, value: u8) {
     match address {
-      0xFF24 => { //ignoring vin
+      0xFF24 => {
+        self.vin = value & 0b1000_1000;
         self.vol_left = ((value & 0b0111_0000) >> 4) as i16;
         self.vol_right = (value & 0b0000_0111) as i16;
       },
@@ -287,4 +469,184 @@ impl Mixer {
 
     (self.vol_left * left, self.vol_right * right)
   }
-}
\ No newline at end of file
+}
+
+impl SaveState for Apu {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.enabled);
+    writer.write_usize(self.timer_step);
+    self.channel_1.save_state(writer);
+    self.channel_2.save_state(writer);
+    self.channel_3.save_state(writer);
+    self.channel_4.save_state(writer);
+    self.mixer.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.enabled = reader.read_bool()?;
+    self.blip_left.clear(); //the samples that were not read yet are lost
+    self.blip_right.clear();
+    self.clock = 0;
+    self.amplitude = (0, 0);
+    self.timer_step = reader.read_usize()? % 8;
+    self.channel_1.load_state(reader)?;
+    self.channel_2.load_state(reader)?;
+    self.channel_3.load_state(reader)?;
+    self.channel_4.load_state(reader)?;
+    self.mixer.load_state(reader)
+  }
+}
+
+impl SaveState for VolumeEnvelope {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_i16(self.volume);
+    writer.write_u8(self.read_byte());
+    writer.write_usize(self.counter);
+    writer.write_bool(self.running);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.volume = reader.read_i16()?;
+    self.write_byte(reader.read_u8()?, false);
+    self.counter = reader.read_usize()?;
+    self.running = reader.read_bool()?;
+    Ok(())
+  }
+}
+
+impl SaveState for LengthCounter {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.enabled);
+    writer.write_u16(self.counter);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.enabled = reader.read_bool()?;
+    self.counter = reader.read_u16()?.min(self.max);
+    Ok(())
+  }
+}
+
+impl SaveState for Mixer {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_u8(self.read_byte(0xFF24));
+    writer.write_u8(self.read_byte(0xFF25));
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.write_byte(0xFF24, reader.read_u8()?);
+    self.write_byte(0xFF25, reader.read_u8()?);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn registers_read_back_with_the_unused_bits_set()
+  {
+    let mut apu = Apu::new(HardwareModel::DMG);
+    apu.write_byte(0xFF11, 0x85);
+    apu.write_byte(0xFF13, 0x12);
+    apu.write_byte(0xFF1A, 0x80);
+    apu.write_byte(0xFF1C, 0x20);
+    apu.write_byte(0xFF30, 0x4A);
+
+    assert_eq!(apu.read_byte(0xFF11), 0xBF);
+    assert_eq!(apu.read_byte(0xFF13), 0xFF);
+    assert_eq!(apu.read_byte(0xFF1A), 0xFF);
+    assert_eq!(apu.read_byte(0xFF1C), 0xBF);
+    assert_eq!(apu.read_byte(0xFF15), 0xFF);
+    assert_eq!(apu.read_byte(0xFF30), 0x4A);
+    assert_eq!(apu.read_byte(0xFF26), 0xF0);
+
+    apu.write_byte(0xFF12, 0xF0);
+    apu.write_byte(0xFF14, 0x80); //trigger
+    assert_eq!(apu.read_byte(0xFF26), 0xF1);
+    apu.write_byte(0xFF12, 0x00); //the DAC turns the channel off
+    assert_eq!(apu.read_byte(0xFF26), 0xF0);
+  }
+
+  #[test]
+  fn power_off_clears_the_registers_and_blocks_writes()
+  {
+    let mut apu = Apu::new(HardwareModel::DMG);
+    apu.write_byte(0xFF24, 0x77);
+    apu.write_byte(0xFF26, 0x00);
+    assert_eq!(apu.read_byte(0xFF24), 0x00);
+    assert_eq!(apu.read_byte(0xFF26), 0x70);
+
+    apu.write_byte(0xFF24, 0x77);
+    apu.write_byte(0xFF11, 0xC0); //only the length is written on the DMG
+    apu.write_byte(0xFF26, 0x80);
+    assert_eq!(apu.read_byte(0xFF24), 0x00);
+    assert_eq!(apu.read_byte(0xFF11), 0x3F);
+  }
+
+  #[test]
+  fn enabling_the_length_in_the_first_half_clocks_it_once_more()
+  {
+    let mut apu = Apu::new(HardwareModel::DMG);
+    apu.write_byte(0xFF12, 0xF0);
+    apu.write_byte(0xFF11, 0x3F); //length 1
+    apu.write_byte(0xFF14, 0x80);
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x01);
+
+    apu.write_byte(0xFF14, 0x40); //the next step doesn't clock the length - it runs out right away
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x00);
+  }
+
+  #[test]
+  fn sweep_overflow_turns_channel_1_off()
+  {
+    let mut apu = Apu::new(HardwareModel::DMG);
+    apu.write_byte(0xFF12, 0xF0);
+    apu.write_byte(0xFF10, 0x11); //period 1, addition, shift 1
+    apu.write_byte(0xFF13, 0x00);
+    apu.write_byte(0xFF14, 0x85); //0x500 + 0x280 is fine but the next one overflows
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x01);
+
+    for _ in 0 .. 3 { //step 2 clocks the sweep
+      apu.step_frame_sequencer();
+    }
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x00);
+
+    apu.write_byte(0xFF10, 0x19); //subtraction
+    apu.write_byte(0xFF14, 0x85);
+    for _ in 0 .. 4 {
+      apu.step_frame_sequencer();
+    }
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x01);
+    apu.write_byte(0xFF10, 0x11); //addition after a subtraction was calculated
+    assert_eq!(apu.read_byte(0xFF26) & 0x01, 0x00);
+  }
+
+  #[test]
+  fn writing_the_envelope_while_playing_changes_the_volume()
+  {
+    let mut envelope = VolumeEnvelope::new();
+    envelope.write_byte(0x53, false); //volume 5 decreasing
+    envelope.reset();
+    envelope.write_byte(0x53, true);
+    assert_eq!(envelope.get_volume(), 7);
+
+    envelope.write_byte(0x50, true); //the period was not 0
+    assert_eq!(envelope.get_volume(), 9);
+    envelope.write_byte(0x58, true); //a running envelope with period 0 adds one and the new direction inverts it
+    assert_eq!(envelope.get_volume(), 6);
+  }
+
+  #[test]
+  fn the_sample_rate_can_be_changed()
+  {
+    let mut apu = Apu::new(HardwareModel::DMG);
+    apu.set_sample_rate(22050);
+    apu.do_ticks(CPU_FREQUENCY / 10);
+
+    let samples = apu.get_audio_buffer().len() / 2;
+    assert!((2200 ..= 2205).contains(&samples));
+  }
+}
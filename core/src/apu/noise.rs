@@ -1,6 +1,7 @@
 extern crate rand;
-use crate::apu::VolumeEnvelope;
+use crate::apu::{VolumeEnvelope, LengthCounter};
 use self::rand::Rng;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 pub struct Noise {
   enabled: bool,
@@ -8,9 +9,8 @@ pub struct Noise {
   period: usize,
   lfsr: u16, //gameboy has a 15bit lfsr - 16bit is good enough :)
   short: bool, //gameboy has 15bit/7bit - 16/8 for us
-  length: u16,
-  duration: u16,
-  length_enabled: bool,
+  polynomial: u8, //NR43
+  length: LengthCounter,
   volume_envelope: VolumeEnvelope
 }
 
@@ -22,9 +22,8 @@ impl Noise {
       period: 1,
       lfsr: rand::thread_rng().gen(),
       short: false,
-      length: 0,
-      duration: 0,
-      length_enabled: false,
+      polynomial: 0,
+      length: LengthCounter::new(64),
       volume_envelope: VolumeEnvelope::new()
     }
   }
@@ -33,32 +32,38 @@ impl Noise {
     self.enabled
   }
 
+  //the write only bits are masked by the apu
   pub fn read_byte(&self, address: u16) -> u8 {
     match address {
-      0xFF20 => self.length as u8,
       0xFF21 => self.volume_envelope.read_byte(),
+      0xFF22 => self.polynomial,
+      0xFF23 if self.length.enabled => 0b0100_0000,
       _ => 0
     }
   }
 
-  pub fn write_byte(&mut self, address: u16, value: u8) {
+  //extra_length_clock is set when the frame sequencer does not clock the length counter next
+  pub fn write_byte(&mut self, address: u16, value: u8, extra_length_clock: bool) {
     match address {
-      0xFF20 => self.length = (value & 0b0001_1111) as u16,
-      0xFF21 => self.volume_envelope.write_byte(value),
+      0xFF20 => self.load_length(value),
+      0xFF21 => {
+        self.volume_envelope.write_byte(value, self.enabled);
+        if !self.volume_envelope.dac_enabled() { //turning the DAC off turns the channel off
+          self.enabled = false;
+        }
+      },
       0xFF22 => {
-        self.short = value & 0b0000_1000 == 0b0000_1000;
-        let divider = match value & 0b0000_0111 {
-          0 => 8,
-          n => n as usize * 16
-        };
-        self.period = divider << (value >> 4);
+        self.polynomial = value;
+        self.update_period();
       },
       0xFF23 => {
-        self.length_enabled = value & 0b0100_0000 == 0b0100_0000;
+        let trigger = value & 0b1000_0000 == 0b1000_0000;
+        if !self.length.write_control(value & 0b0100_0000 == 0b0100_0000, trigger, extra_length_clock) {
+          self.enabled = false;
+        }
 
-        if value & 0b1000_0000 == 0b1000_0000 {
-          self.enabled = true;
-          self.duration = self.length;
+        if trigger {
+          self.enabled = self.volume_envelope.dac_enabled();
           self.volume_envelope.reset();
         }
       },
@@ -66,6 +71,30 @@ impl Noise {
     }
   }
 
+  fn update_period(&mut self) {
+    self.short = self.polynomial & 0b0000_1000 == 0b0000_1000;
+    let divider = match self.polynomial & 0b0000_0111 {
+      0 => 8,
+      n => n as usize * 16
+    };
+    self.period = divider << (self.polynomial >> 4);
+  }
+
+  pub fn load_length(&mut self, value: u8) {
+    self.length.load(value & 0b0011_1111);
+  }
+
+  //everything but the length counter is cleared - the DMG keeps it
+  pub fn power_off(&mut self, keep_length: bool) {
+    let length = self.length.counter;
+    let lfsr = self.lfsr;
+    *self = Noise::new();
+    self.lfsr = lfsr;
+    if keep_length {
+      self.length.counter = length;
+    }
+  }
+
   pub fn do_ticks(&mut self, ticks: usize) {
     self.counter += ticks;
 
@@ -93,13 +122,34 @@ impl Noise {
   }
 
   pub fn timer_step(&mut self) {
-    if self.enabled && self.length_enabled {
-      self.duration -= 1;
-      self.enabled = self.duration > 0;
+    if !self.length.step() {
+      self.enabled = false;
     }
   }
 
   pub fn envelope_step(&mut self) {
     self.volume_envelope.step();
   }
-}
\ No newline at end of file
+}
+
+impl SaveState for Noise {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.enabled);
+    writer.write_usize(self.counter);
+    writer.write_u16(self.lfsr);
+    writer.write_u8(self.polynomial);
+    self.length.save_state(writer);
+    self.volume_envelope.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.enabled = reader.read_bool()?;
+    let counter = reader.read_usize()?;
+    self.lfsr = reader.read_u16()?;
+    self.polynomial = reader.read_u8()?;
+    self.update_period(); //the period is derived so a broken state can't stop the timer
+    self.counter = counter % self.period;
+    self.length.load_state(reader)?;
+    self.volume_envelope.load_state(reader)
+  }
+}
@@ -1,13 +1,12 @@
-use crate::apu::VolumeEnvelope;
+use crate::apu::{VolumeEnvelope, LengthCounter};
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 const WAVE_PATTERN: [[i16;8];4] = [[-1,1,1,1,1,1,1,1],[-1,-1,1,1,1,1,1,1],[-1,-1,-1,-1,1,1,1,1],[-1,-1,-1,-1,-1,-1,1,1]];
 
 pub struct Tone {
   enabled: bool,
   duty: usize,
-  length_enabled: bool,
-  length: usize, //Sound Length = (64-t1)*(1/256) seconds
-  duration: usize,
+  length: LengthCounter, //Sound Length = (64-t1)*(1/256) seconds
   frequency: u16, //Frequency = 131072/(2048-x) Hz
   counter: usize,
   phase: usize,   //which position in the waveform array
@@ -20,10 +19,8 @@ impl Tone {
   pub fn new() -> Tone {
     Tone {
       enabled: false,
-      duty: 2,
-      length_enabled: false,
-      length: 0,
-      duration: 0,
+      duty: 0,
+      length: LengthCounter::new(64),
       frequency: 2048,
       counter: 0,
       phase: 0,
@@ -37,44 +34,46 @@ impl Tone {
     self.enabled
   }
 
+  //the write only bits are masked by the apu
   pub fn read_byte(&self, address: u16) -> u8 {
     match address {
       0xFF10 => self.sweep.read_byte(),
-      0xFF11 | 0xFF16 => {
-        0
-      },
+      0xFF11 | 0xFF16 => (self.duty as u8) << 6,
       0xFF12 | 0xFF17 => self.volume_envelope.read_byte(),
-      0xFF13 | 0xFF18 => {
-        0
-      },
-      0xFF14 | 0xFF19 => {
-        0
-      },
+      0xFF14 | 0xFF19 if self.length.enabled => 0b0100_0000,
       _ => 0
     }
   }
 
-  pub fn write_byte(&mut self, address: u16, value: u8) {
+  //extra_length_clock is set when the frame sequencer does not clock the length counter next
+  pub fn write_byte(&mut self, address: u16, value: u8, extra_length_clock: bool) {
     match address {
-      0xFF10 => self.sweep.write_byte(value),
+      0xFF10 => self.enabled &= self.sweep.write_byte(value), //clearing negate after a subtraction turns the channel off
       0xFF11 | 0xFF16 => {
-        self.length = 64 - (value & 0b0011_1111) as usize;
+        self.load_length(value);
         self.duty = ((value & 0b1100_0000) >> 6) as usize;
       },
-      0xFF12 | 0xFF17 => self.volume_envelope.write_byte(value),
+      0xFF12 | 0xFF17 => {
+        self.volume_envelope.write_byte(value, self.enabled);
+        if !self.volume_envelope.dac_enabled() { //turning the DAC off turns the channel off
+          self.enabled = false;
+        }
+      },
       0xFF13 | 0xFF18 => {
         self.frequency = (self.frequency & 0xFF00) | value as u16;
         self.update_period();
-        self.duration = self.length;
       },
       0xFF14 | 0xFF19 => {
         self.frequency = (self.frequency & 0x00FF) | (((value & 0b0000_0111) as u16) << 8);
         self.update_period();
-        self.length_enabled = value & 0b0100_0000 == 0b0100_0000;
 
-        if value & 0b1000_0000 == 0b1000_0000 {
-          self.enabled = true;
-          self.duration = self.length;
+        let trigger = value & 0b1000_0000 == 0b1000_0000;
+        if !self.length.write_control(value & 0b0100_0000 == 0b0100_0000, trigger, extra_length_clock) {
+          self.enabled = false;
+        }
+
+        if trigger {
+          self.enabled = self.volume_envelope.dac_enabled() && self.sweep.trigger(self.frequency);
           self.volume_envelope.reset();
         }
       },
@@ -82,10 +81,27 @@ impl Tone {
     }
   }
 
+  //the boot sound leaves the channel on with its envelope faded out to 0
+  pub fn finish_boot_sound(&mut self) {
+    self.enabled = self.volume_envelope.dac_enabled();
+  }
+
+  pub fn load_length(&mut self, value: u8) {
+    self.length.load(value & 0b0011_1111);
+  }
+
+  //everything but the length counter is cleared - the DMG keeps it
+  pub fn power_off(&mut self, keep_length: bool) {
+    let length = self.length.counter;
+    *self = Tone::new();
+    if keep_length {
+      self.length.counter = length;
+    }
+  }
+
   pub fn timer_step(&mut self) {
-    if self.enabled && self.length_enabled {
-      self.duration -= 1;
-      self.enabled = self.duration > 0;
+    if !self.length.step() {
+      self.enabled = false;
     }
   }
 
@@ -94,8 +110,10 @@ impl Tone {
   }
 
   pub fn sweep_step(&mut self) {
-      self.frequency = self.sweep.get_frequency(self.frequency);
-      self.update_period();
+    if self.enabled && !self.sweep.step(&mut self.frequency) {
+      self.enabled = false;
+    }
+    self.update_period();
   }
 
   pub fn do_ticks(&mut self, ticks: usize) {
@@ -124,11 +142,19 @@ impl Tone {
   }
 }
 
+/*
+  the sweep works on a shadow copy of the frequency that is taken when the channel is triggered.
+  every calculation checks for an overflow above 2047 which turns channel 1 off - even the second one after the new frequency was written.
+  switching from subtraction to addition after a subtraction was calculated turns the channel off as well.
+*/
 struct Sweep {
   counter: usize,
   period: usize,
   subtraction: bool,
-  shift: usize
+  shift: usize,
+  enabled: bool,
+  shadow_frequency: u16,
+  subtracted: bool //a frequency was calculated in subtraction mode since the trigger
 }
 
 impl Sweep {
@@ -137,7 +163,10 @@ impl Sweep {
       counter: 0,
       period: 0,
       subtraction: false,
-      shift: 0
+      shift: 0,
+      enabled: false,
+      shadow_frequency: 0,
+      subtracted: false
     }
   }
 
@@ -145,31 +174,102 @@ impl Sweep {
     (self.period << 4 | if self.subtraction { 0b0000_1000 } else { 0 } | self.shift) as u8
   }
 
-  pub fn write_byte(&mut self, value: u8) {
+  //false if the channel is turned off
+  pub fn write_byte(&mut self, value: u8) -> bool {
     self.period = ((value & 0b0111_0000) as usize) >> 4;
-    self.subtraction = value & 0b0000_1000 == 0b0000_1000;
+    let subtraction = value & 0b0000_1000 == 0b0000_1000;
+    let keep_playing = subtraction || !self.subtraction || !self.subtracted;
+    self.subtraction = subtraction;
     self.shift = (value & 0b0000_0111) as usize;
+    keep_playing
   }
 
-  pub fn get_frequency(&mut self, frequency: u16) -> u16 {
-    if self.period > 0 {
-      self.counter += 1;
+  //false if the channel is turned off right away
+  pub fn trigger(&mut self, frequency: u16) -> bool {
+    self.shadow_frequency = frequency;
+    self.counter = if self.period > 0 { self.period } else { 8 };
+    self.enabled = self.period > 0 || self.shift > 0;
+    self.subtracted = false;
+    self.shift == 0 || self.calculate() <= 2047
+  }
+
+  fn calculate(&mut self) -> u16 {
+    let offset = self.shadow_frequency >> self.shift;
+    if self.subtraction {  //X(t) = X(t-1) +/- X(t-1)/2^n
+      self.subtracted = true;
+      self.shadow_frequency - offset
+    } else {
+      self.shadow_frequency + offset
+    }
+  }
 
-      if self.counter >= self.period {
-        let offset = frequency >> self.shift;
-        let new_frequency = if self.subtraction {  //X(t) = X(t-1) +/- X(t-1)/2^n
-          frequency - offset
-        } else {
-          frequency + offset
-        };
+  //false if the channel is turned off
+  pub fn step(&mut self, frequency: &mut u16) -> bool {
+    if self.counter > 0 {
+      self.counter -= 1;
+    }
+
+    if self.counter == 0 {
+      self.counter = if self.period > 0 { self.period } else { 8 }; //a period of 0 counts as 8 without calculating
 
-        if new_frequency > 2048 {
-          return 2048
-        } else {
-          return new_frequency
+      if self.enabled && self.period > 0 {
+        let new_frequency = self.calculate();
+        if new_frequency > 2047 {
+          return false
+        }
+
+        if self.shift > 0 {
+          self.shadow_frequency = new_frequency;
+          *frequency = new_frequency;
+          return self.calculate() <= 2047
         }
       }
     }
-    frequency
+    true
+  }
+}
+
+impl SaveState for Tone {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.enabled);
+    writer.write_usize(self.duty);
+    self.length.save_state(writer);
+    writer.write_u16(self.frequency);
+    writer.write_usize(self.counter);
+    writer.write_usize(self.phase);
+    self.volume_envelope.save_state(writer);
+    self.sweep.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.enabled = reader.read_bool()?;
+    self.duty = reader.read_usize()? & 0x03;
+    self.length.load_state(reader)?;
+    self.frequency = reader.read_u16()?;
+    self.update_period(); //the period is derived so a broken state can't stop the timer
+    self.counter = reader.read_usize()? % self.period;
+    self.phase = reader.read_usize()? % 8;
+    self.volume_envelope.load_state(reader)?;
+    self.sweep.load_state(reader)
+  }
+}
+
+impl SaveState for Sweep {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_usize(self.counter);
+    writer.write_u8(self.read_byte());
+    writer.write_bool(self.enabled);
+    writer.write_u16(self.shadow_frequency);
+    writer.write_bool(self.subtracted);
   }
-}
\ No newline at end of file
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.counter = reader.read_usize()?;
+    self.subtracted = false;
+    self.write_byte(reader.read_u8()?);
+    self.enabled = reader.read_bool()?;
+    self.shadow_frequency = reader.read_u16()? & 0x07FF;
+    self.subtracted = reader.read_bool()?;
+    Ok(())
+  }
+}
@@ -1,29 +1,30 @@
+use crate::apu::LengthCounter;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+
 pub struct Wave {
   enabled: bool,
-  length: usize,
-  duration: usize,
-  volume: usize, //shift right
+  dac_enabled: bool, //NR30 bit 7
+  length: LengthCounter,
+  volume: usize, //0 mute, 1 100%, 2 50%, 3 25%
   frequency: u16,
   counter: usize,
   period: usize,
-  length_enabled: bool,
   cursor: usize,
-  wave_pattern: [i16; 32]
+  wave_ram: [u8; 16] //32 4 bit samples - the upper nibble is played first
 }
 
 impl Wave {
   pub fn new() -> Wave {
     Wave {
       enabled: false,
-      length: 0,
-      duration: 0,
-      wave_pattern: [0; 32],
+      dac_enabled: false,
+      length: LengthCounter::new(256),
       volume: 0,
       frequency: 2048,
       counter: 0,
       period: 1,
       cursor: 0,
-      length_enabled: false,
+      wave_ram: [0; 16]
     }
   }
 
@@ -31,39 +32,74 @@ impl Wave {
     self.enabled
   }
 
-  pub fn read_byte(&self, _address: u16) -> u8 {
-    0
+  //the write only bits are masked by the apu
+  pub fn read_byte(&self, address: u16) -> u8 {
+    match address {
+      0xFF1A if self.dac_enabled => 0b1000_0000,
+      0xFF1C => (self.volume as u8) << 5,
+      0xFF1E if self.length.enabled => 0b0100_0000,
+      0xFF30 ..= 0xFF3F => self.wave_ram[self.wave_ram_offset(address)],
+      _ => 0
+    }
   }
 
-  pub fn write_byte(&mut self, address: u16, value: u8) {
+  //extra_length_clock is set when the frame sequencer does not clock the length counter next
+  pub fn write_byte(&mut self, address: u16, value: u8, extra_length_clock: bool) {
     match address {
-      0xFF1A => self.enabled = value & 0b1000_000 == 0b1000_0000,
-      0xFF1B => self.length = value as usize,
+      0xFF1A => {
+        self.dac_enabled = value & 0b1000_0000 == 0b1000_0000;
+        if !self.dac_enabled { //turning the DAC off turns the channel off
+          self.enabled = false;
+        }
+      },
+      0xFF1B => self.load_length(value),
       0xFF1C => self.volume = ((value & 0b0110_0000) as usize) >> 5,
       0xFF1D => {
         self.frequency = (self.frequency & 0xFF00) | value as u16;
         self.update_period();
-        self.duration = self.length;
       },
       0xFF1E => {
         self.frequency = (self.frequency & 0x00FF) | (((value & 0b0000_0111) as u16) << 8);
         self.update_period();
-        self.length_enabled = value & 0b0100_0000 == 0b0100_0000;
 
-        if value & 0b1000_0000 == 0b1000_0000 {
-          self.enabled = true;
-          self.duration = self.length;
+        let trigger = value & 0b1000_0000 == 0b1000_0000;
+        if !self.length.write_control(value & 0b0100_0000 == 0b0100_0000, trigger, extra_length_clock) {
+          self.enabled = false;
+        }
+
+        if trigger {
+          self.enabled = self.dac_enabled;
+          self.cursor = 0;
         }
       },
       0xFF30 ..= 0xFF3F => {
-        let offset = address as usize - 0xFF30;
-        self.wave_pattern[offset * 2] = (((value & 0xF0) >> 4) as i16) - 8;
-        self.wave_pattern[(offset * 2) + 1] = ((value & 0xF) as i16) - 8;
+        let offset = self.wave_ram_offset(address);
+        self.wave_ram[offset] = value;
       },
       _ => ()
     }
   }
 
+  //while the channel plays the cpu reaches the byte that is played instead of the one it addressed
+  fn wave_ram_offset(&self, address: u16) -> usize {
+    if self.enabled { self.cursor / 2 } else { address as usize - 0xFF30 }
+  }
+
+  pub fn load_length(&mut self, value: u8) {
+    self.length.load(value);
+  }
+
+  //everything but the wave ram and the length counter is cleared - the DMG keeps the length counter
+  pub fn power_off(&mut self, keep_length: bool) {
+    let length = self.length.counter;
+    let wave_ram = self.wave_ram;
+    *self = Wave::new();
+    self.wave_ram = wave_ram;
+    if keep_length {
+      self.length.counter = length;
+    }
+  }
+
   pub fn do_ticks(&mut self, ticks: usize) {
     self.counter += ticks;
 
@@ -74,15 +110,16 @@ impl Wave {
   }
 
   pub fn timer_step(&mut self) {
-    if self.enabled && self.length_enabled {
-      self.duration -= 1;
-      self.enabled = self.duration > 0;
+    if !self.length.step() {
+      self.enabled = false;
     }
   }
 
   pub fn get_sample(&self) -> i16 {
     if self.enabled && self.volume > 0 {
-      self.wave_pattern[self.cursor] >> self.volume
+      let byte = self.wave_ram[self.cursor / 2];
+      let sample = if self.cursor.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+      (sample as i16 - 8) >> (self.volume - 1)
     } else {
       0
     }
@@ -95,4 +132,29 @@ impl Wave {
   fn frequency_to_period(frequency: u16) -> usize {
     if frequency >= 2048 { 1 } else { (2048 - frequency as usize) * 2 }
   }
-}
\ No newline at end of file
+}
+
+impl SaveState for Wave {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.enabled);
+    writer.write_bool(self.dac_enabled);
+    self.length.save_state(writer);
+    writer.write_usize(self.volume);
+    writer.write_u16(self.frequency);
+    writer.write_usize(self.counter);
+    writer.write_usize(self.cursor);
+    writer.write_bytes(&self.wave_ram);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.enabled = reader.read_bool()?;
+    self.dac_enabled = reader.read_bool()?;
+    self.length.load_state(reader)?;
+    self.volume = reader.read_usize()? & 0x03;
+    self.frequency = reader.read_u16()?;
+    self.update_period(); //the period is derived so a broken state can't stop the timer
+    self.counter = reader.read_usize()? % self.period;
+    self.cursor = reader.read_usize()? % 32;
+    reader.read_bytes(&mut self.wave_ram)
+  }
+}
@@ -1,13 +1,15 @@
-mod registers;
+pub(crate) mod registers;
 mod alu;
 mod op_codes;
 mod op_codes_cb;
 
 use crate::mmu::Mmu;
-use crate::GBKeyEvent;
 use crate::cpu::registers::{Registers, RegisterName8, RegisterName16, FlagRegister};
-use std::sync::mpsc::Sender;
 use crate::mbc::Mbc;
+use crate::model::HardwareModel;
+use crate::serial::SerialSink;
+use crate::link::LinkCable;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 pub enum OpCodeResult {
   Executed(usize),
@@ -22,104 +24,257 @@ pub struct Cpu {
   registers: Registers,
   mmu: Mmu,
   halted: bool,
+  stopped: bool, //STOP halts everything until a button is pressed
   ime: bool, // interrupt master enable - set by DI and EI
   ei_requested: usize, //EI has one cycle delay
+  instruction_ticks: usize, //ticks of the running instruction - every memory access and internal cycle takes 4
+  elapsed_ticks: usize, //ticks the rest of the system advanced since the last call of tick - counted at normal speed
+  instruction_address: u16, //where the running instruction started
+  log_access_violations: bool, //print every access the ppu blocks
 }
 
 impl Cpu {
-  pub fn new(rom: Box<dyn Mbc>, video_sender: Sender<Vec<u8>>, audio_sender: Sender<Vec<i16>>) -> Cpu {
+  pub fn new(rom: Box<dyn Mbc>, boot_rom: Option<Vec<u8>>, model: HardwareModel) -> Cpu {
+    let registers = if boot_rom.is_some() {
+      Registers::power_up()
+    } else {
+      model.post_boot_registers(rom.as_ref()) //skip the boot rom and start with the state it leaves behind - games check A to detect the model
+    };
+    let mmu = Mmu::new(rom, boot_rom, model);
+
     Cpu {
-      registers: Registers::new(),
-      mmu: Mmu::new(rom, video_sender, audio_sender),
+      registers,
+      mmu,
       halted: false,
+      stopped: false,
       ime: false, //interrupt master enable
       ei_requested: 0, //enable interrupt requested - in the original gameboy the enabling of the interrupts took two cycles (see tick)
+      instruction_ticks: 0,
+      elapsed_ticks: 0,
+      instruction_address: 0,
+      log_access_violations: false,
     }
   }
 
-  pub fn tick(&mut self) -> usize { //, input_state: [bool; 8]) -> usize {
-    //self.mmu.set_joypad_state(input_state);
-
+  pub fn tick(&mut self) -> usize {
     self.ei_requested = match self.ei_requested {
       2 => 1,
       1 => { self.ime = true; 0 },
       _ => 0
     };
 
-    self.handle_irq();
+    self.elapsed_ticks = 0;
 
-    let ticks = if !self.halted {
-      self.do_cycle()
-    } else {
-      4
-    };
+    if self.stopped { //the clock is stopped - only the joypad is alive
+      self.handle_irq();
+      return 4
+    }
+
+    if !self.handle_irq() {
+      if !self.halted {
+        self.do_cycle();
+      } else {
+        self.cycle();
+      }
+    }
 
-    self.mmu.do_ticks(ticks);
+    loop { //the cpu is stopped while a HDMA copies to vram
+      let stall = self.mmu.take_dma_stall();
+      if stall == 0 {
+        break
+      }
+      self.elapsed_ticks += self.mmu.do_ticks(stall);
+    }
 
-    ticks
+    self.elapsed_ticks
   }
 
-  fn handle_irq(&mut self) {
+  //returns true if an interrupt was dispatched
+  fn handle_irq(&mut self) -> bool {
     self.mmu.process_irq_requests(); //loads the irq requests into 0xFF0F
 
     let irq_requested = self.mmu.read_byte(0xFF0F);
+    if irq_requested & 0x10 == 0x10 {
+      self.stopped = false; //a button press ends STOP even if the interrupt is disabled
+    }
     let irq = self.mmu.read_byte(0xFFFF) & irq_requested & 0x1F;
-    if irq > 0 { //there was an interrupt
-      self.halted = false; //end halt when an interrupt occurs
+    if irq == 0 {
+      return false
+    }
+
+    self.halted = false; //end halt when an interrupt occurs
+    if !self.ime {
+      return false
+    }
+    self.ime = false; //don´t allow new interrupts until we handled this one
+
+    /*
+      the dispatch takes 5 M-cycles: 2 internal cycles, the pc is pushed high byte first and the handler is entered
+      the interrupt is selected after the high byte was pushed - if that write hits IE and disables it the cpu jumps to 0x0000
+    */
+    self.cycle();
+    self.cycle();
+    let pc = self.registers.pc;
+    self.registers.sp = self.registers.sp.wrapping_sub(1);
+    self.write(self.registers.sp, (pc >> 8) as u8);
+
+    let irq = self.mmu.read_byte(0xFFFF) & self.mmu.read_byte(0xFF0F) & 0x1F;
+    self.registers.sp = self.registers.sp.wrapping_sub(1);
+    self.write(self.registers.sp, pc as u8);
+
+    if irq == 0 {
+      self.registers.pc = 0x0000;
+    } else {
+      let irq_num = irq.trailing_zeros(); //0 vblank, 1 stat, 2 timer, 3 serial, 4 joypad
+      self.registers.pc = (0x0040 + 8 * irq_num) as u16; // jump to the interrupt handler
+      let irq_requested = self.mmu.read_byte(0xFF0F);
+      self.mmu.write_byte(0xFF0F, irq_requested & !(1 << irq_num));  //reset the irq request - like res
+    }
+    self.cycle();
 
-      if self.ime { //if interrupts are enabled, handle them
-        self.ime = false; //don´t allow new interrupts until we handled this one
+    true
+  }
 
-        let irq_num = irq.trailing_zeros(); //0 vblank, 1 stat, 2 timer, 3 serial, 4 joypad
+  pub fn set_buttons(&mut self, buttons: [bool; 8]) {
+    self.mmu.joypad.set_buttons(buttons);
+  }
 
-        self.push(self.registers.pc);
-        self.registers.pc = (0x0040 + 8 * irq_num) as u16; // jump to the interrupt handler
+  pub fn get_frame_buffer(&self) -> &[u16] {
+    self.mmu.get_frame_buffer()
+  }
 
-        self.mmu.write_byte(0xFF0F, irq_requested & !(1 << irq_num));  //reset the irq request - like res
+  pub fn get_audio_buffer(&self) -> &[i16] {
+    self.mmu.get_audio_buffer()
+  }
 
-      }
-    }
+  pub fn take_frame_ready(&mut self) -> bool {
+    self.mmu.take_frame_ready()
+  }
+
+  pub fn clear_audio_buffer(&mut self) {
+    self.mmu.clear_audio_buffer();
+  }
+
+  pub fn save_data(&self) -> Option<Vec<u8>> {
+    self.mmu.save_data()
+  }
+
+  pub fn save_data_changed(&mut self) -> bool {
+    self.mmu.save_data_changed()
+  }
+
+  pub fn set_serial_sink(&mut self, sink: Option<Box<dyn SerialSink>>) {
+    self.mmu.set_serial_sink(sink);
+  }
+
+  pub fn set_link_cable(&mut self, link: Option<Box<dyn LinkCable>>) {
+    self.mmu.set_link_cable(link);
+  }
+
+  pub fn set_log_access_violations(&mut self, enabled: bool) {
+    self.log_access_violations = enabled;
   }
 
-  pub fn process_input_event(&mut self, event: GBKeyEvent) {
-    self.mmu.joypad.receive_event(event);
+  pub fn set_sample_rate(&mut self, sample_rate: usize) {
+    self.mmu.set_sample_rate(sample_rate);
   }
 
-  fn do_cycle(&mut self) -> usize {
+  pub fn set_pixel_fifo(&mut self, pixel_fifo: bool) {
+    self.mmu.set_pixel_fifo(pixel_fifo);
+  }
+
+  pub fn rom_name(&self) -> String {
+    self.mmu.rom_name()
+  }
+
+  pub fn rom_checksum(&self) -> u32 {
+    self.mmu.rom_checksum()
+  }
+
+  pub fn model(&self) -> HardwareModel {
+    self.mmu.model()
+  }
+
+  #[cfg(test)]
+  pub(crate) fn registers(&self) -> &Registers {
+    &self.registers
+  }
+
+  /*
+    every memory access takes one M-cycle of 4 ticks and the rest of the system is advanced before the access happens
+    internal cycles that are not at the end of an instruction are done explicitly - the remaining ones are added when the instruction finished
+  */
+  fn do_cycle(&mut self) {
     let current_address = self.registers.pc;
+    self.instruction_address = current_address;
+    self.instruction_ticks = 0;
     let op_code = self.fetch_byte();
 
     //println!("do_cycle: {:#04X} @ {:#06X}", op_code, current_address);
 
     match op_codes::execute(op_code, self) {
-      OpCodeResult::Executed(ticks) => { ticks },
-      OpCodeResult::UnknownOpCode => { println!("Unknown command {:#04X} at {:#06X}", op_code, current_address); self.halted = true; 4 } //NOOP on unknown opcodes
+      OpCodeResult::Executed(ticks) => {
+        while self.instruction_ticks < ticks {
+          self.cycle();
+        }
+      },
+      OpCodeResult::UnknownOpCode => { println!("Unknown command {:#04X} at {:#06X}", op_code, current_address); self.halted = true; } //NOOP on unknown opcodes
+    }
+  }
+
+  //one M-cycle without memory access
+  fn cycle(&mut self) {
+    self.instruction_ticks += 4;
+    self.elapsed_ticks += self.mmu.do_ticks(4);
+  }
+
+  fn read(&mut self, address: u16) -> u8 {
+    self.cycle();
+    self.check_access(address, "read");
+    self.mmu.read_byte(address)
+  }
+
+  fn write(&mut self, address: u16, value: u8) {
+    self.cycle();
+    self.check_access(address, "write");
+    self.mmu.write_byte(address, value);
+  }
+
+  //games that access vram while the ppu draws look fine with an emulator that doesn't block it - the log helps to find them
+  fn check_access(&self, address: u16, access: &str) {
+    if self.log_access_violations && self.mmu.blocked_by_ppu(address) {
+      println!("Blocked {} at {:#06X} from PC {:#06X} in LY {}", access, address, self.instruction_address, self.mmu.read_byte(0xFF44));
     }
   }
 
   fn fetch_byte(&mut self) -> u8 {
-    let res = self.mmu.read_byte(self.registers.pc);
-    self.registers.pc += 1;
+    let res = self.read(self.registers.pc);
+    self.registers.pc = self.registers.pc.wrapping_add(1);
     res
   }
 
   fn fetch_word(&mut self) -> u16 {
-    let res = self.mmu.read_word(self.registers.pc);
-    self.registers.pc += 2;
-    res
+    let low = self.fetch_byte() as u16; //LSB first
+    let high = self.fetch_byte() as u16;
+    high << 8 | low
   }
 
   fn push(&mut self, value: u16) {
-    self.registers.sp -= 2; //stack grows down from 0xFFFE and stores words
+    self.cycle(); //sp is decremented before the first write
     //println!("pushing {:06X} to   {:06X}", value, self.registers.sp);
-    self.mmu.write_word(self.registers.sp, value);
+    self.registers.sp = self.registers.sp.wrapping_sub(1); //stack grows down from 0xFFFE and stores the high byte first
+    self.write(self.registers.sp, (value >> 8) as u8);
+    self.registers.sp = self.registers.sp.wrapping_sub(1);
+    self.write(self.registers.sp, value as u8);
   }
 
   fn pop(&mut self) -> u16 {
-    let result = self.mmu.read_word(self.registers.sp);
-    //println!("popping {:06X} from {:06X}", result, self.registers.sp);
-    self.registers.sp += 2;
-    result
+    let low = self.read(self.registers.sp) as u16;
+    self.registers.sp = self.registers.sp.wrapping_add(1);
+    let high = self.read(self.registers.sp) as u16;
+    self.registers.sp = self.registers.sp.wrapping_add(1);
+    //println!("popping {:06X} from {:06X}", high << 8 | low, self.registers.sp);
+    high << 8 | low
   }
 
   fn call(&mut self, address: u16) {
@@ -130,11 +285,13 @@ impl Cpu {
 
   fn retrn(&mut self) {
     self.registers.pc = self.pop();
+    self.cycle(); //setting pc takes another cycle
   }
 
   fn jump_r(&mut self) {
     let offset = self.fetch_byte();
     self.registers.pc = self.registers.pc.wrapping_add(offset as i8 as i16 as u16);
+    self.cycle();
   }
 
   fn execute(&mut self, op: UnaryOperation8, arg: RegisterName8) {
@@ -144,9 +301,9 @@ impl Cpu {
   }
 
   fn execute_hl(&mut self, op: UnaryOperation8) {
-    let original_value = self.mmu.read_byte(self.registers.get_hl());
+    let original_value = self.read(self.registers.get_hl());
     let new_value = op(&mut self.registers, original_value);
-    self.mmu.write_byte(self.registers.get_hl(), new_value);
+    self.write(self.registers.get_hl(), new_value);
   }
 
   fn execute_binary(&mut self, op: BinaryOperation8, arg: RegisterName8) {
@@ -166,4 +323,75 @@ impl Cpu {
     let result = op(&mut self.registers, value1, value2);
     self.registers.set_hl(result);
   }
-}
\ No newline at end of file
+}
+
+impl SaveState for Cpu {
+  fn save_state(&self, writer: &mut StateWriter) {
+    self.registers.save_state(writer);
+    writer.write_bool(self.halted);
+    writer.write_bool(self.stopped);
+    writer.write_bool(self.ime);
+    writer.write_usize(self.ei_requested);
+    self.mmu.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.registers.load_state(reader)?;
+    self.halted = reader.read_bool()?;
+    self.stopped = reader.read_bool()?;
+    self.ime = reader.read_bool()?;
+    self.ei_requested = reader.read_usize()?;
+    self.mmu.load_state(reader)
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use crate::mbc::load_rom_from_buffer;
+  use crate::mbc::header::CartridgeHeader;
+
+  fn cpu_with_program(program: &[u8]) -> Cpu {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0100 .. 0x0100 + program.len()].copy_from_slice(program);
+    rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+    Cpu::new(load_rom_from_buffer(rom).unwrap(), None, HardwareModel::DMG)
+  }
+
+  #[test]
+  fn instructions_take_one_m_cycle_per_access()
+  {
+    let mut cpu = cpu_with_program(&[
+      0x00, //NOP
+      0x21, 0x00, 0xC0, //LD HL,0xC000
+      0x34, //INC (HL)
+      0xBE, //CP (HL)
+      0xCB, 0x46, //BIT 0,(HL)
+      0xCB, 0x06, //RLC (HL)
+      0xC5, //PUSH BC
+      0xC1, //POP BC
+      0xCD, 0x12, 0x01, //CALL 0x0112
+      0xC3, 0x00, 0x00, //JP 0x0000
+      0xC9 //RET
+    ]);
+
+    for &expected in [4, 12, 12, 8, 12, 16, 16, 12, 24, 16].iter() {
+      assert_eq!(cpu.tick(), expected);
+    }
+    assert_eq!(cpu.registers.pc, 0x010F);
+    assert_eq!(cpu.tick(), 16);
+  }
+
+  #[test]
+  fn push_writes_the_high_byte_first()
+  {
+    let mut cpu = cpu_with_program(&[0xC5]); //PUSH BC
+    cpu.registers.set_bc(0x1234);
+    cpu.registers.sp = 0x0000; //the high byte lands in IE
+    cpu.tick();
+
+    assert_eq!(cpu.mmu.read_byte(0xFFFF), 0x12);
+    assert_eq!(cpu.mmu.read_byte(0xFFFE), 0x34);
+  }
+}
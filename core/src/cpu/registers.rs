@@ -1,3 +1,5 @@
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+
 #[derive(Debug, Copy, Clone)]
 pub enum CpuFlag {
   Z = 0b1000_0000, //zero
@@ -47,18 +49,19 @@ pub trait FlagRegister {
 }
 
 impl Registers {
-  pub fn new() -> Registers {
+  //the state at power on - the boot rom starts at 0x0000 and sets everything up
+  pub fn power_up() -> Registers {
     Registers {
-      a: 0x01,
-      f: 0xB0,
+      a: 0x00,
+      f: 0x00,
       b: 0x00,
-      c: 0x13,
+      c: 0x00,
       d: 0x00,
-      e: 0xD8,
-      h: 0x01,
-      l: 0x4D,
-      sp: 0xFFFF, //first stack address is 0xFFFE
-      pc: 0x0100
+      e: 0x00,
+      h: 0x00,
+      l: 0x00,
+      sp: 0x0000,
+      pc: 0x0000
     }
   }
 
@@ -167,6 +170,27 @@ impl FlagRegister for Registers {
   fn get_raw(&self) -> u8 { self.f }
 }
 
+impl SaveState for Registers {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_u16(self.get_af());
+    writer.write_u16(self.get_bc());
+    writer.write_u16(self.get_de());
+    writer.write_u16(self.get_hl());
+    writer.write_u16(self.sp);
+    writer.write_u16(self.pc);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.set_af(reader.read_u16()?);
+    self.set_bc(reader.read_u16()?);
+    self.set_de(reader.read_u16()?);
+    self.set_hl(reader.read_u16()?);
+    self.sp = reader.read_u16()?;
+    self.pc = reader.read_u16()?;
+    Ok(())
+  }
+}
+
 #[cfg(test)]
 mod test
 {
@@ -175,7 +199,8 @@ mod test
   #[test]
   fn wide_registers()
   {
-    let mut test_registers = Registers::new();
+    let mut test_registers = Registers::power_up();
+    test_registers.set_af(0x01B0);
 
     test_registers.a = 0x14;
     test_registers.b = 0x15;
@@ -195,7 +220,7 @@ mod test
     test_registers.set_de(0x4444);
     test_registers.set_hl(0x5555);
 
-    assert_eq!(test_registers.get_af(), 0x22B0);
+    assert_eq!(test_registers.get_af(), 0x2200); //the flags are written as well
     assert_eq!(test_registers.get_bc(), 0x3333);
     assert_eq!(test_registers.get_de(), 0x4444);
     assert_eq!(test_registers.get_hl(), 0x5555);
@@ -203,7 +228,7 @@ mod test
 
   #[test]
   fn test_hl_sepcial() {
-    let mut test_registers = Registers::new();
+    let mut test_registers = Registers::power_up();
 
     test_registers.set_hl(0x1234);
     assert_eq!(test_registers.get_hld(), 0x1234);
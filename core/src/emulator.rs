@@ -0,0 +1,181 @@
+use crate::cpu::Cpu;
+use crate::mbc::Mbc;
+use crate::serial::SerialSink;
+use crate::link::LinkCable;
+use crate::model::HardwareModel;
+use crate::state;
+use crate::state::StateError;
+
+const CYCLES_PER_FRAME: usize = 70224; //154 lines * 456 ticks
+
+//what the machine produced during a run - the slices stay valid until the emulator runs again
+pub struct Output<'a> {
+  pub frame: &'a [u16], //SCREEN_WIDTH * SCREEN_HEIGHT 15 bit colors of the last finished frame - bits 0-4 red, 5-9 green, 10-14 blue
+  pub audio: &'a [i16], //interleaved stereo samples at the rate set with set_sample_rate - AUDIO_OUTPUT_FREQUENCY by default
+  pub new_frame: bool, //a frame was finished during this run
+  pub cycles: usize //ticks that were executed - counted at normal speed so double speed does not run twice as long
+}
+
+pub struct Emulator {
+  cpu: Cpu
+}
+
+impl Emulator {
+  //without a boot rom the emulation starts with the state the boot rom of the model leaves behind
+  pub fn new(rom: Box<dyn Mbc>, boot_rom: Option<Vec<u8>>, model: HardwareModel) -> Emulator {
+    Emulator {
+      cpu: Cpu::new(rom, boot_rom, model)
+    }
+  }
+
+  //runs until the ppu finished the next frame - a blank one if the lcd is off
+  pub fn run_frame(&mut self) -> Output<'_> {
+    self.cpu.clear_audio_buffer();
+
+    let mut cycles = 0;
+    let mut new_frame = false;
+    while !new_frame && cycles < CYCLES_PER_FRAME {
+      cycles += self.cpu.tick();
+      new_frame = self.cpu.take_frame_ready();
+    }
+
+    self.output(new_frame, cycles)
+  }
+
+  //runs at least the given number of cpu ticks - instructions are not interrupted so it can be a few more
+  pub fn run_cycles(&mut self, cycles: usize) -> Output<'_> {
+    self.cpu.clear_audio_buffer();
+
+    let mut executed = 0;
+    let mut new_frame = false;
+    while executed < cycles {
+      executed += self.cpu.tick();
+      new_frame |= self.cpu.take_frame_ready();
+    }
+
+    self.output(new_frame, executed)
+  }
+
+  fn output(&self, new_frame: bool, cycles: usize) -> Output<'_> {
+    Output {
+      frame: self.cpu.get_frame_buffer(),
+      audio: self.cpu.get_audio_buffer(),
+      new_frame,
+      cycles
+    }
+  }
+
+  //the pressed state of the buttons indexed by GBKeyCode
+  pub fn set_buttons(&mut self, buttons: [bool; 8]) {
+    self.cpu.set_buttons(buttons);
+  }
+
+  pub fn rom_name(&self) -> String {
+    self.cpu.rom_name()
+  }
+
+  pub fn save_data(&self) -> Option<Vec<u8>> {
+    self.cpu.save_data()
+  }
+
+  //true once after the game wrote to its battery backed ram - the frontend only has to write the save then
+  pub fn save_data_changed(&mut self) -> bool {
+    self.cpu.save_data_changed()
+  }
+
+  //receives every byte the game sends over the serial port - None to disconnect it
+  pub fn set_serial_sink(&mut self, sink: Option<Box<dyn SerialSink>>) {
+    self.cpu.set_serial_sink(sink);
+  }
+
+  //connects the serial port to a second game boy - None unplugs the cable
+  pub fn set_link_cable(&mut self, link: Option<Box<dyn LinkCable>>) {
+    self.cpu.set_link_cable(link);
+  }
+
+  //prints every vram, OAM or palette access the ppu blocks with the address of the instruction and the current line
+  pub fn set_log_access_violations(&mut self, enabled: bool) {
+    self.cpu.set_log_access_violations(enabled);
+  }
+
+  //the sample rate of the audio output - the audio device decides how much of it is buffered
+  pub fn set_sample_rate(&mut self, sample_rate: usize) {
+    self.cpu.set_sample_rate(sample_rate);
+  }
+
+  //renders with the pixel fifo - accurate for games that change registers in the middle of a line but slower
+  pub fn set_pixel_fifo(&mut self, pixel_fifo: bool) {
+    self.cpu.set_pixel_fifo(pixel_fifo);
+  }
+
+  pub fn save_state(&self) -> Vec<u8> {
+    state::save(&self.cpu)
+  }
+
+  pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+    state::load(&mut self.cpu, data)
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use crate::mbc::load_rom_from_buffer;
+  use crate::mbc::header::CartridgeHeader;
+
+  //counts in A and B and writes A to the work ram in an endless loop
+  fn emulator(title: &[u8], model: HardwareModel) -> Emulator {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0100 .. 0x0109].copy_from_slice(&[0x21, 0x00, 0xC0, 0x3C, 0x04, 0x77, 0x2C, 0x18, 0xFA]); //LD HL,C000 - INC A - INC B - LD (HL),A - INC L - JR -6
+    rom[0x0134 .. 0x0134 + title.len()].copy_from_slice(title);
+    rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+    Emulator::new(load_rom_from_buffer(rom).unwrap(), None, model)
+  }
+
+  fn snapshot(emulator: &mut Emulator) -> (Vec<u16>, [u16; 6]) {
+    let frame = emulator.run_frame().frame.to_vec();
+    let registers = emulator.cpu.registers();
+    (frame, [registers.get_af(), registers.get_bc(), registers.get_de(), registers.get_hl(), registers.sp, registers.pc])
+  }
+
+  #[test]
+  fn loading_a_state_replays_the_same_frames()
+  {
+    let mut emulator = emulator(b"STATE", HardwareModel::DMG);
+    for _ in 0 .. 3 {
+      emulator.run_frame();
+    }
+    emulator.run_cycles(1234); //save in the middle of a frame
+
+    let state = emulator.save_state();
+    let expected = snapshot(&mut emulator);
+    for _ in 0 .. 5 {
+      emulator.run_frame();
+    }
+
+    emulator.load_state(&state).unwrap();
+    assert_eq!(snapshot(&mut emulator), expected);
+  }
+
+  #[test]
+  fn states_of_other_games_and_models_are_rejected()
+  {
+    let mut emulator = emulator(b"STATE", HardwareModel::DMG);
+    emulator.run_cycles(5000);
+    let expected = emulator.save_state();
+
+    let other_game = self::emulator(b"OTHER", HardwareModel::DMG);
+    assert!(matches!(emulator.load_state(&other_game.save_state()), Err(StateError::WrongRom(_))));
+    assert_eq!(emulator.save_state(), expected);
+
+    let other_model = self::emulator(b"STATE", HardwareModel::CGB);
+    assert!(matches!(emulator.load_state(&other_model.save_state()), Err(StateError::WrongModel(_))));
+    assert_eq!(emulator.save_state(), expected);
+
+    let mut truncated = expected.clone(); //a broken state restores the machine from the backup
+    truncated.truncate(expected.len() / 2);
+    assert!(matches!(emulator.load_state(&truncated), Err(StateError::Truncated)));
+    assert_eq!(emulator.save_state(), expected);
+  }
+}
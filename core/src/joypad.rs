@@ -1,11 +1,11 @@
 use crate::GBKeyCode;
-use crate::GBKeyEvent;
-use crate::GBKeyState;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 pub struct Joypad {
-  pub irq_joypad: bool, //interrupt is true when input has changed
+  pub irq_joypad: bool, //set when one of the input lines goes from high to low
   state: [bool;8], //the state of the 8 buttons
-  selector: bool, //true = buttons, false = directions
+  select_buttons: bool, //P15 is low
+  select_directions: bool, //P14 is low
 }
 
 impl Joypad {
@@ -13,7 +13,8 @@ impl Joypad {
     Joypad {
       irq_joypad: false,
       state:[false; 8],
-      selector: false
+      select_buttons: true, //P1 reads 0xCF after the boot rom
+      select_directions: true
     }
   }
 
@@ -26,38 +27,111 @@ impl Joypad {
   Bit 2 - P12 Input Up    or Select   (0=Pressed) (Read Only)
   Bit 1 - P11 Input Left  or Button B (0=Pressed) (Read Only)
   Bit 0 - P10 Input Right or Button A (0=Pressed) (Read Only)
+  both groups can be selected at the same time - the input lines then combine the pressed buttons of both
   */
 
   pub fn read(&self) -> u8 {
-    let mut joypad = 0x00;
-    if self.selector {
-      joypad |= 0x20; //P15
-      if self.state[GBKeyCode::A as usize] { joypad |= 0x01 };
-      if self.state[GBKeyCode::B as usize] { joypad |= 0x02 };
-      if self.state[GBKeyCode::Select as usize] { joypad |= 0x04 };
-      if self.state[GBKeyCode::Start as usize] { joypad |= 0x08 };
-    } else {
-      joypad |= 0x10; //P14
-      if self.state[GBKeyCode::Right as usize] { joypad |= 0x01 };
-      if self.state[GBKeyCode::Left as usize] { joypad |= 0x02 };
-      if self.state[GBKeyCode::Up as usize] { joypad |= 0x04 };
-      if self.state[GBKeyCode::Down as usize] { joypad |= 0x08 };
+    0xC0 | //the unused bits read 1
+    (if self.select_buttons { 0x00 } else { 0x20 }) |
+    (if self.select_directions { 0x00 } else { 0x10 }) |
+    self.input_lines()
+  }
+
+  pub fn write(&mut self, value: u8) {
+    let lines = self.input_lines();
+    self.select_buttons = value & 0x20 == 0x00;
+    self.select_directions = value & 0x10 == 0x00;
+    self.update_irq(lines);
+  }
+
+  pub fn set_buttons(&mut self, buttons: [bool; 8]) {
+    let lines = self.input_lines();
+    self.state = buttons;
+    self.update_irq(lines);
+  }
+
+  fn update_irq(&mut self, old_lines: u8) {
+    self.irq_joypad |= old_lines & !self.input_lines() != 0; //the interrupt is triggered when a line goes low
+  }
+
+  //P10-P13 - a line is low when a pressed button of a selected group is connected to it
+  fn input_lines(&self) -> u8 {
+    let mut pressed = 0x00;
+    if self.select_buttons {
+      if self.state[GBKeyCode::A as usize] { pressed |= 0x01 };
+      if self.state[GBKeyCode::B as usize] { pressed |= 0x02 };
+      if self.state[GBKeyCode::Select as usize] { pressed |= 0x04 };
+      if self.state[GBKeyCode::Start as usize] { pressed |= 0x08 };
+    }
+    if self.select_directions {
+      if self.state[GBKeyCode::Right as usize] { pressed |= 0x01 };
+      if self.state[GBKeyCode::Left as usize] { pressed |= 0x02 };
+      if self.state[GBKeyCode::Up as usize] { pressed |= 0x04 };
+      if self.state[GBKeyCode::Down as usize] { pressed |= 0x08 };
     }
 
-    !joypad //the gameboy has the input array inverted
+    !pressed & 0x0F //the gameboy has the input array inverted
   }
+}
 
-  pub fn write(&mut self, value: u8) {
-    if (!value & 0x20) == 0x20 { self.selector = true; }
-    else if (!value & 0x10) == 0x10 { self.selector = false; }
+impl SaveState for Joypad { //the button state is not saved - it belongs to the player and not to the machine
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.irq_joypad);
+    writer.write_bool(self.select_buttons);
+    writer.write_bool(self.select_directions);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.irq_joypad = reader.read_bool()?;
+    self.select_buttons = reader.read_bool()?;
+    self.select_directions = reader.read_bool()?;
+    Ok(())
   }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  fn pressed(buttons: &[GBKeyCode]) -> [bool; 8] {
+    let mut state = [false; 8];
+    for button in buttons {
+      state[*button as usize] = true;
+    }
+    state
+  }
+
+  #[test]
+  fn selection_lines_are_independent()
+  {
+    let mut joypad = Joypad::new();
+    joypad.set_buttons(pressed(&[GBKeyCode::A, GBKeyCode::Down]));
 
-  pub fn receive_event(&mut self, event: GBKeyEvent) {
-    let key_index = event.key_code as usize;
-    self.irq_joypad |= !self.state[key_index] && event.state == GBKeyState::KeyDown;
-    self.state[key_index] = match event.state {
-      GBKeyState::KeyDown => true,
-      GBKeyState::KeyUp => false
-    };
+    joypad.write(0x10); //buttons
+    assert_eq!(joypad.read(), 0xDE);
+    joypad.write(0x20); //directions
+    assert_eq!(joypad.read(), 0xE7);
+    joypad.write(0x00); //both
+    assert_eq!(joypad.read(), 0xC6);
+    joypad.write(0x30); //none
+    assert_eq!(joypad.read(), 0xFF);
   }
-}
\ No newline at end of file
+
+  #[test]
+  fn interrupt_on_falling_input_line()
+  {
+    let mut joypad = Joypad::new();
+    joypad.write(0x20); //directions only
+
+    joypad.set_buttons(pressed(&[GBKeyCode::Start]));
+    assert!(!joypad.irq_joypad);
+
+    joypad.set_buttons(pressed(&[GBKeyCode::Start, GBKeyCode::Up]));
+    assert!(joypad.irq_joypad);
+
+    joypad.irq_joypad = false;
+    joypad.write(0x00); //selecting the buttons pulls the start line low
+    assert!(joypad.irq_joypad);
+  }
+}
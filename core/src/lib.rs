@@ -1,33 +1,26 @@
 pub mod cpu;
 pub mod mbc;
+pub mod state;
+pub mod emulator;
+pub mod serial;
+pub mod link;
+pub mod model;
 
 mod mmu;
 mod joypad;
 mod timer;
 mod ppu;
 mod apu;
-mod serial;
 
 pub const CPU_FREQUENCY: usize = 4_194_304; //4.194304 MHz
 
 pub const SCREEN_WIDTH: usize = 160;
 pub const SCREEN_HEIGHT: usize = 144;
 
-pub const AUDIO_OUTPUT_FREQUENCY: usize = 48_000;
-pub const AUDIO_BUFFER_SIZE: usize = 1600;
+pub const AUDIO_OUTPUT_FREQUENCY: usize = 48_000; //the default sample rate - it can be changed with Emulator::set_sample_rate
+pub const AUDIO_BUFFER_SIZE: usize = 1600; //the default samples per channel the audio device plays at once - smaller buffers lower the latency
 
-use std::sync::mpsc::Receiver;
-use std::time::{Instant, Duration};
-
-use crate::cpu::Cpu;
-
-#[derive(Debug)]
-pub enum GBEvent {
-  KeyEvent(GBKeyEvent),
-  Quit
-}
-
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub enum GBKeyCode {
   Up = 0,
   Down,
@@ -38,40 +31,3 @@ pub enum GBKeyCode {
   Start,
   Select
 }
-
-#[derive(PartialEq, Debug)]
-pub enum GBKeyState {
-  KeyUp,
-  KeyDown
-}
-
-#[derive(Debug)]
-pub struct GBKeyEvent {
-  pub key_code: GBKeyCode,
-  pub state: GBKeyState
-}
-
-pub fn main_loop(mut cpu: Cpu, input_receiver: Receiver<GBEvent>) {
-  let mut last_second = Instant::now();
-  let one_second = Duration::from_secs(1);
-  let mut ticks_per_second : usize = 0;
-
-  'running: loop {
-    for event in input_receiver.try_iter() {
-      match event {
-        GBEvent::KeyEvent(key_event) => cpu.process_input_event(key_event),
-        GBEvent::Quit => break 'running,
-      }
-    }
-
-    ticks_per_second += cpu.tick();
-
-    if last_second.elapsed() >= one_second {
-      if ticks_per_second < CPU_FREQUENCY {
-        println!("CPU slow! {} ticks should be {}", ticks_per_second, CPU_FREQUENCY);
-      }
-      ticks_per_second = 0;
-      last_second = Instant::now();
-    }
-  }
-}
\ No newline at end of file
@@ -0,0 +1,195 @@
+use std::io;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+
+const SYNC_INTERVAL: u64 = 2048; //ticks both sides may run ahead of each other - 4 bit times of the normal serial clock
+
+const MESSAGE_TRANSFER: u8 = 0x01;
+const MESSAGE_REPLY: u8 = 0x02;
+const MESSAGE_SYNC: u8 = 0x03;
+
+//the other end of the link cable - the serial port of a second game boy
+pub trait LinkCable : Send {
+  fn send(&mut self, value: u8); //this side starts a transfer with its internal clock
+  fn receive(&mut self) -> u8; //the byte of the partner for the transfer started with send - waits for the partner
+  fn sync(&mut self, ticks: usize, data: u8) -> Option<u8>; //advances the clock and returns the byte of a transfer clocked by the partner - data is the answer
+}
+
+/*
+  link cable over a byte stream between two emulators
+    Transfer(byte) - the sender started a transfer with its internal clock
+    Reply(byte)    - the answer of the partner with the content of its serial register
+    Sync(ticks)    - the sender reached the given tick count
+  every SYNC_INTERVAL ticks both sides send a Sync and wait for the Sync of the partner so the clocks stay in lockstep.
+  transfers are answered while waiting which keeps the master from blocking the slave.
+  when the connection breaks the cable behaves as if it was unplugged.
+*/
+pub struct StreamLink<S: Read + Write + Send> {
+  stream: S,
+  connected: bool,
+  ticks: u64,
+  next_sync: u64,
+  partner_ticks: u64,
+  data: u8, //what we answer to a transfer of the partner
+  incoming: Option<u8>, //a transfer of the partner that was answered but not yet handed to the serial port
+  reply: Option<u8> //the answer to our own transfer
+}
+
+impl<S: Read + Write + Send> StreamLink<S> {
+  pub fn new(stream: S) -> StreamLink<S> {
+    StreamLink {
+      stream,
+      connected: true,
+      ticks: 0,
+      next_sync: SYNC_INTERVAL,
+      partner_ticks: 0,
+      data: 0xFF,
+      incoming: None,
+      reply: None
+    }
+  }
+
+  pub fn connected(&self) -> bool {
+    self.connected
+  }
+
+  fn write_message(&mut self, message: &[u8]) {
+    if self.connected && self.stream.write_all(message).and_then(|_| self.stream.flush()).is_err() {
+      self.connected = false;
+    }
+  }
+
+  fn read_message(&mut self) {
+    if let Err(_error) = self.try_read_message() {
+      self.connected = false;
+    }
+  }
+
+  fn try_read_message(&mut self) -> io::Result<()> {
+    let mut tag = [0u8; 1];
+    self.stream.read_exact(&mut tag)?;
+
+    match tag[0] {
+      MESSAGE_TRANSFER => {
+        let mut value = [0u8; 1];
+        self.stream.read_exact(&mut value)?;
+        self.incoming = Some(value[0]);
+        let data = self.data;
+        self.write_message(&[MESSAGE_REPLY, data]);
+      },
+      MESSAGE_REPLY => {
+        let mut value = [0u8; 1];
+        self.stream.read_exact(&mut value)?;
+        self.reply = Some(value[0]);
+      },
+      MESSAGE_SYNC => {
+        let mut ticks = [0u8; 8];
+        self.stream.read_exact(&mut ticks)?;
+        self.partner_ticks = u64::from_le_bytes(ticks);
+      },
+      _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown link cable message"))
+    }
+
+    Ok(())
+  }
+}
+
+impl<S: Read + Write + Send> LinkCable for StreamLink<S> {
+  fn send(&mut self, value: u8) {
+    self.data = value;
+    self.reply = None;
+    self.write_message(&[MESSAGE_TRANSFER, value]);
+  }
+
+  fn receive(&mut self) -> u8 {
+    while self.connected && self.reply.is_none() {
+      self.read_message();
+    }
+    self.reply.take().unwrap_or(0xFF)
+  }
+
+  fn sync(&mut self, ticks: usize, data: u8) -> Option<u8> {
+    self.data = data;
+    self.ticks += ticks as u64;
+
+    while self.connected && self.ticks >= self.next_sync {
+      let mut message = [MESSAGE_SYNC; 9];
+      message[1..].copy_from_slice(&self.next_sync.to_le_bytes());
+      self.write_message(&message);
+
+      while self.connected && self.partner_ticks < self.next_sync {
+        self.read_message();
+      }
+      self.next_sync += SYNC_INTERVAL;
+    }
+
+    self.incoming.take()
+  }
+}
+
+//waits for the partner to connect
+pub fn host<A: ToSocketAddrs>(address: A) -> io::Result<StreamLink<TcpStream>> {
+  let (stream, _) = TcpListener::bind(address)?.accept()?;
+  stream.set_nodelay(true)?;
+  Ok(StreamLink::new(stream))
+}
+
+pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<StreamLink<TcpStream>> {
+  let stream = TcpStream::connect(address)?;
+  stream.set_nodelay(true)?;
+  Ok(StreamLink::new(stream))
+}
+
+#[cfg(unix)]
+pub fn host_unix<P: AsRef<Path>>(path: P) -> io::Result<StreamLink<UnixStream>> {
+  let _ = std::fs::remove_file(&path); //a socket file left behind by an earlier run
+  let (stream, _) = UnixListener::bind(path)?.accept()?;
+  Ok(StreamLink::new(stream))
+}
+
+#[cfg(unix)]
+pub fn connect_unix<P: AsRef<Path>>(path: P) -> io::Result<StreamLink<UnixStream>> {
+  Ok(StreamLink::new(UnixStream::connect(path)?))
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use crate::serial::Serial;
+  use std::thread;
+
+  fn run_transfer(mut serial: Serial, data: u8, control: u8) -> u8 {
+    serial.write(0xFF01, data);
+    serial.write(0xFF02, control);
+    for _ in 0 .. 4096 { //the same number of ticks on both sides - a transfer takes 4096
+      serial.do_ticks(4);
+    }
+    assert!(serial.irq_serial);
+    serial.read(0xFF01)
+  }
+
+  #[test]
+  fn two_serial_ports_exchange_bytes_over_loopback()
+  {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let slave = thread::spawn(move || {
+      let mut serial = Serial::new(false);
+      serial.set_link(Some(Box::new(StreamLink::new(listener.accept().unwrap().0))));
+      run_transfer(serial, 0x34, 0x80)
+    });
+
+    let mut serial = Serial::new(false);
+    serial.set_link(Some(Box::new(connect(address).unwrap())));
+    let master_received = run_transfer(serial, 0x12, 0x81);
+
+    assert_eq!(master_received, 0x34);
+    assert_eq!(slave.join().unwrap(), 0x12);
+  }
+}
@@ -0,0 +1,375 @@
+use std::fmt;
+
+const ADDR_LOGO: usize = 0x0104;
+const ADDR_TITLE: usize = 0x0134;
+const ADDR_MANUFACTURER_CODE: usize = 0x013F;
+const ADDR_CGB_FLAG: usize = 0x0143;
+const ADDR_NEW_LICENSEE: usize = 0x0144;
+const ADDR_SGB_FLAG: usize = 0x0146;
+const ADDR_CARTRIDGE_TYPE: usize = 0x0147;
+const ADDR_ROM_SIZE: usize = 0x0148;
+const ADDR_RAM_SIZE: usize = 0x0149;
+const ADDR_DESTINATION: usize = 0x014A;
+const ADDR_OLD_LICENSEE: usize = 0x014B;
+const ADDR_VERSION: usize = 0x014C;
+const ADDR_HEADER_CHECKSUM: usize = 0x014D;
+const ADDR_GLOBAL_CHECKSUM: usize = 0x014E;
+
+pub const HEADER_END: usize = 0x0150; //a rom has to be at least this long to contain a header
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+//the boot rom compares the logo in the header with its own copy and locks up if they differ
+const NINTENDO_LOGO: [u8; 48] = [
+  0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+  0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+  0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+  None,
+  Supported, //0x80 - works on DMG and CGB
+  Required //0xC0 - CGB only
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+  Japan,
+  Overseas
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CartridgeType {
+  RomOnly,
+  Mbc1,
+  Mbc1Ram,
+  Mbc1RamBattery,
+  Mbc2,
+  Mbc2Battery,
+  RomRam,
+  RomRamBattery,
+  Mmm01,
+  Mmm01Ram,
+  Mmm01RamBattery,
+  Mbc3TimerBattery,
+  Mbc3TimerRamBattery,
+  Mbc3,
+  Mbc3Ram,
+  Mbc3RamBattery,
+  Mbc5,
+  Mbc5Ram,
+  Mbc5RamBattery,
+  Mbc5Rumble,
+  Mbc5RumbleRam,
+  Mbc5RumbleRamBattery,
+  Mbc6,
+  Mbc7SensorRumbleRamBattery,
+  PocketCamera,
+  BandaiTama5,
+  HuC3,
+  HuC1RamBattery,
+  Unknown(u8)
+}
+
+impl CartridgeType {
+  pub fn from_byte(value: u8) -> CartridgeType {
+    match value {
+      0x00 => CartridgeType::RomOnly,
+      0x01 => CartridgeType::Mbc1,
+      0x02 => CartridgeType::Mbc1Ram,
+      0x03 => CartridgeType::Mbc1RamBattery,
+      0x05 => CartridgeType::Mbc2,
+      0x06 => CartridgeType::Mbc2Battery,
+      0x08 => CartridgeType::RomRam,
+      0x09 => CartridgeType::RomRamBattery,
+      0x0B => CartridgeType::Mmm01,
+      0x0C => CartridgeType::Mmm01Ram,
+      0x0D => CartridgeType::Mmm01RamBattery,
+      0x0F => CartridgeType::Mbc3TimerBattery,
+      0x10 => CartridgeType::Mbc3TimerRamBattery,
+      0x11 => CartridgeType::Mbc3,
+      0x12 => CartridgeType::Mbc3Ram,
+      0x13 => CartridgeType::Mbc3RamBattery,
+      0x19 => CartridgeType::Mbc5,
+      0x1A => CartridgeType::Mbc5Ram,
+      0x1B => CartridgeType::Mbc5RamBattery,
+      0x1C => CartridgeType::Mbc5Rumble,
+      0x1D => CartridgeType::Mbc5RumbleRam,
+      0x1E => CartridgeType::Mbc5RumbleRamBattery,
+      0x20 => CartridgeType::Mbc6,
+      0x22 => CartridgeType::Mbc7SensorRumbleRamBattery,
+      0xFC => CartridgeType::PocketCamera,
+      0xFD => CartridgeType::BandaiTama5,
+      0xFE => CartridgeType::HuC3,
+      0xFF => CartridgeType::HuC1RamBattery,
+      v => CartridgeType::Unknown(v)
+    }
+  }
+
+  pub fn has_battery(&self) -> bool {
+    matches!(self,
+      CartridgeType::Mbc1RamBattery | CartridgeType::Mbc2Battery | CartridgeType::RomRamBattery |
+      CartridgeType::Mmm01RamBattery | CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery |
+      CartridgeType::Mbc3RamBattery | CartridgeType::Mbc5RamBattery | CartridgeType::Mbc5RumbleRamBattery |
+      CartridgeType::Mbc7SensorRumbleRamBattery | CartridgeType::HuC3 | CartridgeType::HuC1RamBattery)
+  }
+
+  pub fn has_timer(&self) -> bool {
+    matches!(self, CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery | CartridgeType::HuC3)
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      CartridgeType::RomOnly => "ROM ONLY",
+      CartridgeType::Mbc1 => "MBC1",
+      CartridgeType::Mbc1Ram => "MBC1+RAM",
+      CartridgeType::Mbc1RamBattery => "MBC1+RAM+BATTERY",
+      CartridgeType::Mbc2 => "MBC2",
+      CartridgeType::Mbc2Battery => "MBC2+BATTERY",
+      CartridgeType::RomRam => "ROM+RAM",
+      CartridgeType::RomRamBattery => "ROM+RAM+BATTERY",
+      CartridgeType::Mmm01 => "MMM01",
+      CartridgeType::Mmm01Ram => "MMM01+RAM",
+      CartridgeType::Mmm01RamBattery => "MMM01+RAM+BATTERY",
+      CartridgeType::Mbc3TimerBattery => "MBC3+TIMER+BATTERY",
+      CartridgeType::Mbc3TimerRamBattery => "MBC3+TIMER+RAM+BATTERY",
+      CartridgeType::Mbc3 => "MBC3",
+      CartridgeType::Mbc3Ram => "MBC3+RAM",
+      CartridgeType::Mbc3RamBattery => "MBC3+RAM+BATTERY",
+      CartridgeType::Mbc5 => "MBC5",
+      CartridgeType::Mbc5Ram => "MBC5+RAM",
+      CartridgeType::Mbc5RamBattery => "MBC5+RAM+BATTERY",
+      CartridgeType::Mbc5Rumble => "MBC5+RUMBLE",
+      CartridgeType::Mbc5RumbleRam => "MBC5+RUMBLE+RAM",
+      CartridgeType::Mbc5RumbleRamBattery => "MBC5+RUMBLE+RAM+BATTERY",
+      CartridgeType::Mbc6 => "MBC6",
+      CartridgeType::Mbc7SensorRumbleRamBattery => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+      CartridgeType::PocketCamera => "POCKET CAMERA",
+      CartridgeType::BandaiTama5 => "BANDAI TAMA5",
+      CartridgeType::HuC3 => "HuC3",
+      CartridgeType::HuC1RamBattery => "HuC1+RAM+BATTERY",
+      CartridgeType::Unknown(_) => "UNKNOWN"
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+  pub logo_valid: bool,
+  pub title: String,
+  pub manufacturer_code: String, //only used by newer cartridges - empty otherwise
+  pub cgb_support: CgbSupport,
+  pub sgb_support: bool,
+  pub old_licensee: u8,
+  pub new_licensee: String, //only used if the old licensee code is 0x33
+  pub cartridge_type_code: u8,
+  pub cartridge_type: CartridgeType,
+  pub rom_size: usize, //in bytes
+  pub ram_size: usize, //in bytes - MBC2 has its ram built in and declares 0
+  pub destination: Destination,
+  pub version: u8,
+  pub header_checksum: u8,
+  pub calculated_header_checksum: u8,
+  pub header_checksum_valid: bool,
+  pub global_checksum: u16,
+  pub global_checksum_valid: bool //not verified by the hardware - many roms have a wrong one
+}
+
+impl CartridgeHeader {
+  //the rom has to contain at least HEADER_END bytes
+  pub fn parse(rom: &[u8]) -> CartridgeHeader {
+    let cgb_support = match rom[ADDR_CGB_FLAG] {
+      0x80 => CgbSupport::Supported,
+      0xC0 => CgbSupport::Required,
+      _ => CgbSupport::None
+    };
+
+    let manufacturer_code = &rom[ADDR_MANUFACTURER_CODE .. ADDR_CGB_FLAG];
+    let has_manufacturer_code = cgb_support != CgbSupport::None && manufacturer_code.iter().all(|c| c.is_ascii_uppercase());
+
+    let title_end = if cgb_support != CgbSupport::None { //the cgb flag and the manufacturer code took over the end of the title
+      if has_manufacturer_code { ADDR_MANUFACTURER_CODE } else { ADDR_CGB_FLAG }
+    } else {
+      ADDR_NEW_LICENSEE
+    };
+
+    let header_checksum = rom[ADDR_HEADER_CHECKSUM];
+    let calculated_header_checksum = CartridgeHeader::calculate_header_checksum(rom);
+    let global_checksum = (rom[ADDR_GLOBAL_CHECKSUM] as u16) << 8 | rom[ADDR_GLOBAL_CHECKSUM + 1] as u16;
+
+    CartridgeHeader {
+      logo_valid: rom[ADDR_LOGO .. ADDR_TITLE] == NINTENDO_LOGO[..],
+      title: ascii(&rom[ADDR_TITLE .. title_end]),
+      manufacturer_code: if has_manufacturer_code { ascii(manufacturer_code) } else { String::new() },
+      cgb_support,
+      sgb_support: rom[ADDR_SGB_FLAG] == 0x03,
+      old_licensee: rom[ADDR_OLD_LICENSEE],
+      new_licensee: ascii(&rom[ADDR_NEW_LICENSEE .. ADDR_SGB_FLAG]),
+      cartridge_type_code: rom[ADDR_CARTRIDGE_TYPE],
+      cartridge_type: CartridgeType::from_byte(rom[ADDR_CARTRIDGE_TYPE]),
+      rom_size: match rom[ADDR_ROM_SIZE] {
+        n @ 0x00 ..= 0x08 => (ROM_BANK_SIZE * 2) << n, //32kB << n
+        0x52 => ROM_BANK_SIZE * 72,
+        0x53 => ROM_BANK_SIZE * 80,
+        0x54 => ROM_BANK_SIZE * 96,
+        _ => rom.len()
+      },
+      ram_size: match rom[ADDR_RAM_SIZE] {
+        0x01 => 0x800,
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x20000,
+        0x05 => 0x10000,
+        _ => 0
+      },
+      destination: if rom[ADDR_DESTINATION] == 0x00 { Destination::Japan } else { Destination::Overseas },
+      version: rom[ADDR_VERSION],
+      header_checksum,
+      calculated_header_checksum,
+      header_checksum_valid: calculated_header_checksum == header_checksum,
+      global_checksum,
+      global_checksum_valid: CartridgeHeader::calculate_global_checksum(rom) == global_checksum
+    }
+  }
+
+  //x = 0: for 0134-014C: x = x - byte - 1 - the boot rom refuses to start if it does not match
+  pub fn calculate_header_checksum(rom: &[u8]) -> u8 {
+    rom[ADDR_TITLE ..= ADDR_VERSION].iter().fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1))
+  }
+
+  //the sum of all bytes of the rom except the two checksum bytes
+  pub fn calculate_global_checksum(rom: &[u8]) -> u16 {
+    rom.iter().enumerate()
+      .filter(|(address, _)| *address != ADDR_GLOBAL_CHECKSUM && *address != ADDR_GLOBAL_CHECKSUM + 1)
+      .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+  }
+
+  pub fn rom_banks(&self) -> usize {
+    (self.rom_size / ROM_BANK_SIZE).max(2)
+  }
+
+  pub fn licensee(&self) -> &'static str {
+    if self.old_licensee == 0x33 {
+      match self.new_licensee.as_str() {
+        "01" => "Nintendo",
+        "08" => "Capcom",
+        "13" => "Electronic Arts",
+        "18" => "Hudson Soft",
+        "20" => "KSS",
+        "28" => "Kemco",
+        "31" => "Nintendo",
+        "34" => "Konami",
+        "41" => "Ubisoft",
+        "51" => "Acclaim",
+        "52" => "Activision",
+        "54" => "Konami",
+        "56" => "LJN",
+        "64" => "LucasArts",
+        "69" => "Electronic Arts",
+        "70" => "Infogrames",
+        "78" => "THQ",
+        "79" => "Accolade",
+        "A4" => "Konami",
+        "B1" => "ASCII",
+        "B2" => "Bandai",
+        "C3" => "Squaresoft",
+        _ => "Unknown"
+      }
+    } else {
+      match self.old_licensee {
+        0x00 => "None",
+        0x01 | 0x31 => "Nintendo",
+        0x08 | 0x38 => "Capcom",
+        0x13 | 0x69 => "Electronic Arts",
+        0x18 => "Hudson Soft",
+        0x28 | 0x7F => "Kemco",
+        0x41 => "Ubisoft",
+        0x51 | 0xB0 => "Acclaim",
+        0x52 => "Activision",
+        0x56 => "LJN",
+        0x64 => "LucasArts",
+        0x70 => "Infogrames",
+        0x79 => "Accolade",
+        0xA4 => "Konami",
+        0xB1 => "ASCII",
+        0xB2 => "Bandai",
+        0xC3 => "Squaresoft",
+        _ => "Unknown"
+      }
+    }
+  }
+}
+
+impl fmt::Display for CartridgeHeader {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "Title:           {}", self.title)?;
+    if !self.manufacturer_code.is_empty() {
+      writeln!(f, "Manufacturer:    {}", self.manufacturer_code)?;
+    }
+    writeln!(f, "Licensee:        {}", self.licensee())?;
+    writeln!(f, "Cartridge type:  {} ({:#04X})", self.cartridge_type.name(), self.cartridge_type_code)?;
+    writeln!(f, "ROM size:        {} kB ({} banks)", self.rom_size / 1024, self.rom_banks())?;
+    writeln!(f, "RAM size:        {} kB", self.ram_size / 1024)?;
+    writeln!(f, "CGB / SGB:       {:?} / {}", self.cgb_support, if self.sgb_support { "Supported" } else { "None" })?;
+    writeln!(f, "Destination:     {:?}", self.destination)?;
+    writeln!(f, "Version:         {}", self.version)?;
+    writeln!(f, "Nintendo logo:   {}", if self.logo_valid { "OK" } else { "MISMATCH" })?;
+    writeln!(f, "Header checksum: {:#04X} {}", self.header_checksum, if self.header_checksum_valid { "OK" } else { "MISMATCH" })?;
+    write!(f, "Global checksum: {:#06X} {}", self.global_checksum, if self.global_checksum_valid { "OK" } else { "MISMATCH" })
+  }
+}
+
+fn ascii(bytes: &[u8]) -> String {
+  bytes.iter().take_while(|c| **c != 0).map(|c| *c as char).collect::<String>().trim_end().to_string()
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  fn test_rom() -> Vec<u8> {
+    let mut rom = vec![0u8; 0x8000];
+    rom[ADDR_LOGO .. ADDR_TITLE].copy_from_slice(&NINTENDO_LOGO);
+    rom[ADDR_TITLE .. ADDR_TITLE + 6].copy_from_slice(b"TETRIS");
+    rom[ADDR_CARTRIDGE_TYPE] = 0x13;
+    rom[ADDR_ROM_SIZE] = 0x01;
+    rom[ADDR_RAM_SIZE] = 0x03;
+    rom[ADDR_DESTINATION] = 0x01;
+    rom[ADDR_HEADER_CHECKSUM] = CartridgeHeader::calculate_header_checksum(&rom);
+    let global_checksum = CartridgeHeader::calculate_global_checksum(&rom);
+    rom[ADDR_GLOBAL_CHECKSUM] = (global_checksum >> 8) as u8;
+    rom[ADDR_GLOBAL_CHECKSUM + 1] = global_checksum as u8;
+    rom
+  }
+
+  #[test]
+  fn header_fields_are_parsed()
+  {
+    let header = CartridgeHeader::parse(&test_rom());
+
+    assert!(header.logo_valid);
+    assert_eq!(header.title, "TETRIS");
+    assert_eq!(header.cartridge_type, CartridgeType::Mbc3RamBattery);
+    assert!(header.cartridge_type.has_battery());
+    assert_eq!(header.rom_size, 0x10000);
+    assert_eq!(header.rom_banks(), 4);
+    assert_eq!(header.ram_size, 0x8000);
+    assert_eq!(header.destination, Destination::Overseas);
+    assert!(header.header_checksum_valid);
+    assert!(header.global_checksum_valid);
+  }
+
+  #[test]
+  fn checksum_mismatches_are_detected()
+  {
+    let mut rom = test_rom();
+    rom[ADDR_VERSION] = 1;
+
+    let header = CartridgeHeader::parse(&rom);
+
+    assert!(!header.header_checksum_valid);
+    assert!(!header.global_checksum_valid);
+  }
+}
@@ -1,13 +1,17 @@
 use crate::mbc::Mbc;
+use crate::mbc::header::CartridgeHeader;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 pub struct Mbc0 {
-  rom: Vec<u8>
+  rom: Vec<u8>,
+  header: CartridgeHeader
 }
 
 impl Mbc0 {
-  pub fn new(buffer: Vec<u8>) -> Mbc0 {
+  pub fn new(buffer: Vec<u8>, header: CartridgeHeader) -> Mbc0 {
     Mbc0 {
-      rom: buffer
+      rom: buffer,
+      header
     }
   }
 }
@@ -27,4 +31,17 @@ impl Mbc for Mbc0 {
   fn write_ram(&mut self, _address: u16, _value: u8) {
 
   }
-}
\ No newline at end of file
+
+  fn header(&self) -> &CartridgeHeader {
+    &self.header
+  }
+}
+
+impl SaveState for Mbc0 { //no registers and no ram
+  fn save_state(&self, _writer: &mut StateWriter) {
+  }
+
+  fn load_state(&mut self, _reader: &mut StateReader) -> Result<(), StateError> {
+    Ok(())
+  }
+}
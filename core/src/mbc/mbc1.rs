@@ -1,9 +1,10 @@
 use crate::mbc::Mbc;
+use crate::mbc::header::CartridgeHeader;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 const ROM_BANK_SIZE: usize = 0x4000;
 
 const RAM_BANK_SIZE: usize = 0x2000;
-const NUM_RAM_BANK: usize = 4;
 
 enum BankingMode {
   ROM,
@@ -13,37 +14,50 @@ enum BankingMode {
 pub struct Mbc1 {
   rom: Vec<u8>,
   selected_rom_bank: usize,
-  ram: [u8; RAM_BANK_SIZE * NUM_RAM_BANK],
+  rom_banks: usize,
+  ram: Vec<u8>, //sized by the header - empty if the cartridge has no ram
   ram_enabled: bool,
   selected_ram_bank: usize,
+  battery: bool,
+  save_data_changed: bool,
+  header: CartridgeHeader,
   banking_mode: BankingMode
 }
 
 impl Mbc1 {
-  pub fn new(buffer: Vec<u8>) -> Mbc1 {
+  pub fn new(buffer: Vec<u8>, header: CartridgeHeader) -> Mbc1 {
     Mbc1 {
+      rom_banks: buffer.len() / ROM_BANK_SIZE,
       rom:buffer,
       selected_rom_bank: 1, //0 is mapped to 0000-3FFF
-      ram: [0; RAM_BANK_SIZE * NUM_RAM_BANK],
+      ram: vec![0; header.ram_size],
       selected_ram_bank: 0,
+      battery: header.cartridge_type.has_battery(),
+      save_data_changed: false,
       ram_enabled: false,
-      banking_mode: BankingMode::ROM
+      banking_mode: BankingMode::ROM,
+      header
     }
   }
+
+  //the ram banks wrap around if the game selects more banks than the cartridge has
+  fn ram_address(&self, address: u16) -> usize {
+    (RAM_BANK_SIZE * self.selected_ram_bank + address as usize) % self.ram.len()
+  }
 }
 
 impl Mbc for Mbc1 {
   fn read_rom(&self, address: u16) -> u8 {
     match address {
       0x0000 ..= 0x3FFF => self.rom[address as usize],
-      0x4000 ..= 0x7FFF => self.rom[ROM_BANK_SIZE * self.selected_rom_bank + (address - 0x4000) as usize],
+      0x4000 ..= 0x7FFF => self.rom[ROM_BANK_SIZE * (self.selected_rom_bank % self.rom_banks) + (address - 0x4000) as usize],
       _ => 0
     }
   }
 
   fn read_ram(&self, address: u16) -> u8 {
-    if self.ram_enabled {
-      self.ram[RAM_BANK_SIZE * self.selected_ram_bank + address as usize]
+    if self.ram_enabled && !self.ram.is_empty() {
+      self.ram[self.ram_address(address)]
     } else {
       0
     }
@@ -69,8 +83,50 @@ impl Mbc for Mbc1 {
   }
 
   fn write_ram(&mut self, address: u16, value: u8) {
-    if self.ram_enabled {
-      self.ram[RAM_BANK_SIZE * self.selected_ram_bank + address as usize] = value;
+    if self.ram_enabled && !self.ram.is_empty() {
+      let address = self.ram_address(address);
+      self.ram[address] = value;
+      self.save_data_changed = true;
     }
   }
-}
\ No newline at end of file
+
+  fn save_data(&self) -> Option<Vec<u8>> {
+    if self.battery {
+      Some(self.ram.clone())
+    } else {
+      None
+    }
+  }
+
+  fn save_data_changed(&mut self) -> bool {
+    std::mem::replace(&mut self.save_data_changed, false)
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    let size = data.len().min(self.ram.len());
+    self.ram[..size].copy_from_slice(&data[..size]);
+  }
+
+  fn header(&self) -> &CartridgeHeader {
+    &self.header
+  }
+}
+
+impl SaveState for Mbc1 {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_usize(self.selected_rom_bank);
+    writer.write_bytes(&self.ram);
+    writer.write_bool(self.ram_enabled);
+    writer.write_usize(self.selected_ram_bank);
+    writer.write_bool(match self.banking_mode { BankingMode::ROM => false, BankingMode::RAM => true });
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.selected_rom_bank = reader.read_usize()?;
+    reader.read_bytes(&mut self.ram)?;
+    self.ram_enabled = reader.read_bool()?;
+    self.selected_ram_bank = reader.read_usize()?;
+    self.banking_mode = if reader.read_bool()? { BankingMode::RAM } else { BankingMode::ROM };
+    Ok(())
+  }
+}
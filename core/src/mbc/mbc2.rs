@@ -1,4 +1,6 @@
 use crate::mbc::Mbc;
+use crate::mbc::header::CartridgeHeader;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
 const ROM_BANK_SIZE: usize = 0x4000;
 const RAM_BANK_SIZE: usize = 0x200;
@@ -6,17 +8,25 @@ const RAM_BANK_SIZE: usize = 0x200;
 pub struct Mbc2 {
   rom: Vec<u8>,
   selected_rom_bank: usize,
-  ram: [u8; RAM_BANK_SIZE],
+  rom_banks: usize,
+  ram: [u8; RAM_BANK_SIZE], //the mbc2 has 512 half bytes built in - the header declares no ram
   ram_enabled: bool,
+  battery: bool,
+  save_data_changed: bool,
+  header: CartridgeHeader
 }
 
 impl Mbc2 {
-  pub fn new(buffer: Vec<u8>) -> Mbc2 {
+  pub fn new(buffer: Vec<u8>, header: CartridgeHeader) -> Mbc2 {
     Mbc2 {
+      rom_banks: buffer.len() / ROM_BANK_SIZE,
       rom:buffer,
       selected_rom_bank: 1, //0 is mapped to 0000-3FFF
       ram: [0; RAM_BANK_SIZE ],
       ram_enabled: false,
+      battery: header.cartridge_type.has_battery(),
+      save_data_changed: false,
+      header
     }
   }
 }
@@ -25,16 +35,17 @@ impl Mbc for Mbc2 {
   fn read_rom(&self, address: u16) -> u8 {
     match address {
       0x0000 ..= 0x3FFF => self.rom[address as usize],
-      0x4000 ..= 0x7FFF => self.rom[ROM_BANK_SIZE * self.selected_rom_bank + (address - 0x4000) as usize],
+      0x4000 ..= 0x7FFF => self.rom[ROM_BANK_SIZE * (self.selected_rom_bank % self.rom_banks) + (address - 0x4000) as usize],
       _ => 0
     }
   }
 
+  //the 512 half bytes repeat over A000-BFFF - only the lower half of each byte is stored
   fn read_ram(&self, address: u16) -> u8 {
     if self.ram_enabled {
-      self.ram[address as usize]
+      0xF0 | self.ram[address as usize & 0x01FF] //the upper half is not connected and reads as 1
     } else {
-      0
+      0xFF
     }
   }
 
@@ -50,7 +61,72 @@ impl Mbc for Mbc2 {
 
   fn write_ram(&mut self, address: u16, value: u8) {
     if self.ram_enabled {
-      self.ram[address as usize] = value;
+      self.ram[address as usize & 0x01FF] = value & 0x0F;
+      self.save_data_changed = true;
     }
   }
-}
\ No newline at end of file
+
+  fn save_data(&self) -> Option<Vec<u8>> {
+    if self.battery {
+      Some(self.ram.to_vec())
+    } else {
+      None
+    }
+  }
+
+  fn save_data_changed(&mut self) -> bool {
+    std::mem::replace(&mut self.save_data_changed, false)
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    for (cell, value) in self.ram.iter_mut().zip(data.iter()) {
+      *cell = value & 0x0F;
+    }
+  }
+
+  fn header(&self) -> &CartridgeHeader {
+    &self.header
+  }
+}
+
+impl SaveState for Mbc2 {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_usize(self.selected_rom_bank);
+    writer.write_bytes(&self.ram);
+    writer.write_bool(self.ram_enabled);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.selected_rom_bank = reader.read_usize()?;
+    reader.read_bytes(&mut self.ram)?;
+    self.ram_enabled = reader.read_bool()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn ram_repeats_over_the_whole_area_and_stores_half_bytes()
+  {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0147] = 0x06; //MBC2 with battery
+    let mut mbc = Mbc2::new(rom.clone(), CartridgeHeader::parse(&rom));
+    mbc.ram_enabled = true;
+
+    assert!(!mbc.save_data_changed());
+    mbc.write_ram(0x1E05, 0xAB); //BE05 is the echo of A005
+
+    assert_eq!(mbc.read_ram(0x0005), 0xFB);
+    assert_eq!(mbc.read_ram(0x0205), 0xFB);
+    assert_eq!(mbc.save_data().unwrap()[0x0005], 0x0B);
+    assert!(mbc.save_data_changed());
+    assert!(!mbc.save_data_changed());
+
+    mbc.ram_enabled = false;
+    assert_eq!(mbc.read_ram(0x0005), 0xFF);
+  }
+}
@@ -0,0 +1,436 @@
+use crate::mbc::Mbc;
+use crate::mbc::header::CartridgeHeader;
+use crate::CPU_FREQUENCY;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+const RAM_BANK_SIZE: usize = 0x2000;
+
+const RTC_DATA_SIZE: usize = 48; //5 registers + 5 latched registers as u32 and a u64 timestamp
+
+pub struct Mbc3 {
+  rom: Vec<u8>,
+  selected_rom_bank: usize,
+  rom_banks: usize,
+  ram: Vec<u8>, //sized by the header - empty if the cartridge has no ram
+  ram_enabled: bool, //also enables access to the rtc registers
+  selected_ram_bank: usize, //0x00-0x03 ram bank, 0x08-0x0C rtc register
+  rtc: Rtc,
+  latched_rtc: Rtc,
+  latch_armed: bool, //the rtc is latched by writing 0x00 and then 0x01 to 6000-7FFF
+  battery: bool,
+  timer: bool, //only MBC3+TIMER cartridges have the rtc
+  save_data_changed: bool,
+  header: CartridgeHeader,
+}
+
+impl Mbc3 {
+  pub fn new(buffer: Vec<u8>, header: CartridgeHeader) -> Mbc3 {
+    Mbc3 {
+      rom_banks: buffer.len() / ROM_BANK_SIZE,
+      rom: buffer,
+      selected_rom_bank: 1, //0 is mapped to 0000-3FFF
+      ram: vec![0; header.ram_size],
+      ram_enabled: false,
+      selected_ram_bank: 0,
+      rtc: Rtc::new(),
+      latched_rtc: Rtc::new(),
+      latch_armed: false,
+      battery: header.cartridge_type.has_battery(),
+      timer: header.cartridge_type.has_timer(),
+      save_data_changed: false,
+      header
+    }
+  }
+
+  //the ram banks wrap around if the game selects more banks than the cartridge has
+  fn ram_address(&self, address: u16) -> usize {
+    (RAM_BANK_SIZE * self.selected_ram_bank + address as usize) % self.ram.len()
+  }
+}
+
+impl Mbc for Mbc3 {
+  fn read_rom(&self, address: u16) -> u8 {
+    match address {
+      0x0000 ..= 0x3FFF => self.rom[address as usize],
+      0x4000 ..= 0x7FFF => self.rom[ROM_BANK_SIZE * (self.selected_rom_bank % self.rom_banks) + (address - 0x4000) as usize],
+      _ => 0
+    }
+  }
+
+  fn read_ram(&self, address: u16) -> u8 {
+    if self.ram_enabled {
+      match self.selected_ram_bank {
+        0x00 ..= 0x03 if !self.ram.is_empty() => self.ram[self.ram_address(address)],
+        0x08 ..= 0x0C if self.timer => self.latched_rtc.read(self.selected_ram_bank),
+        _ => 0xFF
+      }
+    } else {
+      0xFF
+    }
+  }
+
+  fn write_rom(&mut self, address: u16, value: u8) {
+    match address {
+      0x0000 ..= 0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+      0x2000 ..= 0x3FFF => self.selected_rom_bank = match value as usize & 0x7F {
+        0 => 1,
+        n => n
+      }, //7 bits - 0 selects bank 1 like on the MBC1
+      0x4000 ..= 0x5FFF => self.selected_ram_bank = value as usize,
+      0x6000 ..= 0x7FFF => {
+        if self.latch_armed && value == 0x01 {
+          self.latched_rtc = self.rtc.clone();
+        }
+        self.latch_armed = value == 0x00;
+      },
+      _ => ()
+    }
+  }
+
+  fn write_ram(&mut self, address: u16, value: u8) {
+    if self.ram_enabled {
+      match self.selected_ram_bank {
+        0x00 ..= 0x03 if !self.ram.is_empty() => {
+          let address = self.ram_address(address);
+          self.ram[address] = value;
+          self.save_data_changed = true;
+        },
+        0x08 ..= 0x0C if self.timer => {
+          self.rtc.write(self.selected_ram_bank, value);
+          self.latched_rtc.write(self.selected_ram_bank, value);
+          self.save_data_changed = true;
+        },
+        _ => ()
+      }
+    }
+  }
+
+  fn do_ticks(&mut self, ticks: usize) {
+    if self.timer {
+      self.rtc.do_ticks(ticks);
+    }
+  }
+
+  //with a timer the ram is followed by the rtc registers, the latched registers and a timestamp - the layout used by VBA and BGB
+  fn save_data(&self) -> Option<Vec<u8>> {
+    if !self.battery {
+      return None
+    }
+
+    let mut data = Vec::with_capacity(self.ram.len() + RTC_DATA_SIZE);
+    data.extend_from_slice(&self.ram);
+    if !self.timer {
+      return Some(data)
+    }
+
+    for value in self.rtc.registers().iter().chain(self.latched_rtc.registers().iter()) {
+      data.extend_from_slice(&(*value as u32).to_le_bytes());
+    }
+    data.extend_from_slice(&unix_time().to_le_bytes());
+
+    Some(data)
+  }
+
+  fn save_data_changed(&mut self) -> bool {
+    std::mem::replace(&mut self.save_data_changed, false)
+  }
+
+  //restores ram and rtc and lets the rtc catch up with the wall clock time that passed since the data was written
+  //the clock running on doesn't count - the save keeps the time it was written at
+  fn load_save_data(&mut self, data: &[u8]) {
+    let size = data.len().min(self.ram.len());
+    self.ram[..size].copy_from_slice(&data[..size]);
+
+    let rtc_data = &data[size..];
+    if !self.timer || rtc_data.len() < RTC_DATA_SIZE {
+      return
+    }
+
+    let register = |index: usize| rtc_data[index * 4];
+    self.rtc.set_registers([register(0), register(1), register(2), register(3), register(4)]);
+    self.latched_rtc.set_registers([register(5), register(6), register(7), register(8), register(9)]);
+
+    let mut timestamp = [0u8; 8];
+    timestamp.copy_from_slice(&rtc_data[40..48]);
+    let saved_at = u64::from_le_bytes(timestamp);
+
+    self.rtc.advance_seconds(unix_time().saturating_sub(saved_at));
+  }
+
+  fn header(&self) -> &CartridgeHeader {
+    &self.header
+  }
+}
+
+/*
+  08h  RTC S   Seconds   0-59 (0-3Bh)
+  09h  RTC M   Minutes   0-59 (0-3Bh)
+  0Ah  RTC H   Hours     0-23 (0-17h)
+  0Bh  RTC DL  Lower 8 bits of Day Counter (0-FFh)
+  0Ch  RTC DH  Upper 1 bit of Day Counter, Carry Bit, Halt Flag
+        Bit 0  Most significant bit of Day Counter (Bit 8)
+        Bit 6  Halt (0=Active, 1=Stop Timer)
+        Bit 7  Day Counter Carry Bit (1=Counter Overflow)
+*/
+#[derive(Clone)]
+struct Rtc {
+  seconds: u8,
+  minutes: u8,
+  hours: u8,
+  days: u16,
+  halted: bool,
+  carry: bool,
+  counter: usize, //ticks since the last full second
+}
+
+impl Rtc {
+  fn new() -> Rtc {
+    Rtc {
+      seconds: 0,
+      minutes: 0,
+      hours: 0,
+      days: 0,
+      halted: false,
+      carry: false,
+      counter: 0,
+    }
+  }
+
+  fn read(&self, register: usize) -> u8 {
+    match register {
+      0x08 => self.seconds,
+      0x09 => self.minutes,
+      0x0A => self.hours,
+      0x0B => self.days as u8,
+      0x0C => {
+        ((self.days >> 8) as u8 & 0x01) |
+        (if self.halted { 0x40 } else { 0x00 }) |
+        (if self.carry { 0x80 } else { 0x00 })
+      },
+      _ => 0xFF
+    }
+  }
+
+  fn write(&mut self, register: usize, value: u8) {
+    match register {
+      0x08 => { self.seconds = value & 0x3F; self.counter = 0; }, //writing the seconds resets the sub second divider
+      0x09 => self.minutes = value & 0x3F,
+      0x0A => self.hours = value & 0x1F,
+      0x0B => self.days = (self.days & 0x100) | value as u16,
+      0x0C => {
+        self.days = (self.days & 0xFF) | (((value & 0x01) as u16) << 8);
+        self.halted = value & 0x40 == 0x40;
+        self.carry = value & 0x80 == 0x80;
+      },
+      _ => ()
+    }
+  }
+
+  fn registers(&self) -> [u8; 5] {
+    [self.read(0x08), self.read(0x09), self.read(0x0A), self.read(0x0B), self.read(0x0C)]
+  }
+
+  fn set_registers(&mut self, registers: [u8; 5]) {
+    for (i, value) in registers.iter().enumerate() {
+      self.write(0x08 + i, *value);
+    }
+  }
+
+  fn do_ticks(&mut self, ticks: usize) {
+    if !self.halted {
+      self.counter += ticks;
+
+      while self.counter >= CPU_FREQUENCY {
+        self.counter -= CPU_FREQUENCY;
+        self.tick_second();
+      }
+    }
+  }
+
+  //out of range registers are counted up one by one until they are back in range - that takes less than a day
+  fn advance_seconds(&mut self, seconds: u64) {
+    if self.halted {
+      return
+    }
+
+    let mut seconds = seconds.min(MAX_ELAPSED_SECONDS);
+    while seconds > 0 && !(self.seconds < 60 && self.minutes < 60 && self.hours < 24) {
+      self.tick_second();
+      seconds -= 1;
+    }
+
+    let total = seconds + self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600;
+    let days = self.days as u64 + total / 86400;
+
+    self.seconds = (total % 60) as u8;
+    self.minutes = (total / 60 % 60) as u8;
+    self.hours = (total / 3600 % 24) as u8;
+    self.days = (days % 512) as u16;
+    self.carry |= days >= 512;
+  }
+
+  //out of range values count up to the maximum of the register and wrap to 0 without a carry to the next register
+  fn tick_second(&mut self) {
+    self.seconds = (self.seconds + 1) & 0x3F;
+    if self.seconds != 60 {
+      return
+    }
+    self.seconds = 0;
+
+    self.minutes = (self.minutes + 1) & 0x3F;
+    if self.minutes != 60 {
+      return
+    }
+    self.minutes = 0;
+
+    self.hours = (self.hours + 1) & 0x1F;
+    if self.hours != 24 {
+      return
+    }
+    self.hours = 0;
+
+    self.days += 1;
+    if self.days > 0x1FF {
+      self.days = 0;
+      self.carry = true;
+    }
+  }
+}
+
+const MAX_ELAPSED_SECONDS: u64 = 0xFFFF_FFFF; //a broken timestamp in the save can't move the clock further than this
+
+fn unix_time() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+impl SaveState for Mbc3 {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_usize(self.selected_rom_bank);
+    writer.write_bytes(&self.ram);
+    writer.write_bool(self.ram_enabled);
+    writer.write_usize(self.selected_ram_bank);
+    self.rtc.save_state(writer);
+    self.latched_rtc.save_state(writer);
+    writer.write_bool(self.latch_armed);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.selected_rom_bank = reader.read_usize()?;
+    reader.read_bytes(&mut self.ram)?;
+    self.ram_enabled = reader.read_bool()?;
+    self.selected_ram_bank = reader.read_usize()?;
+    self.rtc.load_state(reader)?;
+    self.latched_rtc.load_state(reader)?;
+    self.latch_armed = reader.read_bool()?;
+    Ok(())
+  }
+}
+
+impl SaveState for Rtc {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bytes(&self.registers());
+    writer.write_usize(self.counter);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    let mut registers = [0u8; 5];
+    reader.read_bytes(&mut registers)?;
+    self.set_registers(registers);
+    self.counter = reader.read_usize()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn rtc_rolls_over_into_days_and_carry()
+  {
+    let mut rtc = Rtc::new();
+    rtc.set_registers([59, 59, 23, 0xFF, 0x01]);
+
+    rtc.do_ticks(CPU_FREQUENCY);
+
+    assert_eq!(rtc.registers(), [0, 0, 0, 0, 0x80]);
+  }
+
+  #[test]
+  fn rtc_out_of_range_values_wrap_without_carry()
+  {
+    let mut rtc = Rtc::new();
+    rtc.set_registers([63, 0, 0, 0, 0]);
+
+    rtc.advance_seconds(1);
+
+    assert_eq!(rtc.registers(), [0, 0, 0, 0, 0]);
+  }
+
+  #[test]
+  fn rtc_catches_up_with_a_broken_timestamp()
+  {
+    let mut rtc = Rtc::new();
+    rtc.set_registers([63, 63, 31, 0, 0]);
+
+    rtc.advance_seconds(1_700_000_000); //saved at 0
+
+    let registers = rtc.registers();
+    assert!(registers[0] < 60 && registers[1] < 60 && registers[2] < 24);
+    assert_eq!(registers[4] & 0x80, 0x80); //the day counter overflowed
+  }
+
+  #[test]
+  fn rtc_catches_up_with_elapsed_time()
+  {
+    let mut rtc = Rtc::new();
+    rtc.set_registers([30, 10, 5, 0, 0]);
+
+    rtc.advance_seconds(2 * 86400 + 3600 + 45);
+
+    assert_eq!(rtc.registers(), [15, 11, 6, 2, 0]);
+  }
+
+  #[test]
+  fn latch_copies_the_running_clock()
+  {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0147] = 0x0F; //MBC3+TIMER+BATTERY
+    let header = CartridgeHeader::parse(&rom);
+    let mut mbc = Mbc3::new(rom, header);
+    mbc.write_rom(0x0000, 0x0A);
+    mbc.write_rom(0x4000, 0x08);
+
+    mbc.do_ticks(CPU_FREQUENCY * 3);
+    assert_eq!(mbc.read_ram(0), 0);
+
+    mbc.write_rom(0x6000, 0x00);
+    mbc.write_rom(0x6000, 0x01);
+    assert_eq!(mbc.read_ram(0), 3);
+
+    mbc.do_ticks(CPU_FREQUENCY);
+    assert_eq!(mbc.read_ram(0), 3);
+  }
+
+  #[test]
+  fn cartridges_without_a_timer_save_only_the_ram()
+  {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0147] = 0x13; //MBC3+RAM+BATTERY
+    rom[0x0149] = 0x02; //8KB
+    let header = CartridgeHeader::parse(&rom);
+    let mut mbc = Mbc3::new(rom, header);
+    mbc.write_rom(0x0000, 0x0A);
+    mbc.write_rom(0x4000, 0x08);
+
+    mbc.do_ticks(CPU_FREQUENCY * 3);
+    mbc.write_rom(0x6000, 0x00);
+    mbc.write_rom(0x6000, 0x01);
+    assert_eq!(mbc.read_ram(0), 0xFF);
+    assert_eq!(mbc.save_data().unwrap().len(), 0x2000);
+  }
+}
@@ -1,74 +1,208 @@
 extern crate zip;
 
+pub mod header;
+
 mod mbc0;
 mod mbc1;
 mod mbc2;
 mod mbc3;
 mod mbc5;
 
-use std::fs::File;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::io::Read;
 use zip::ZipArchive;
+use zip::result::ZipError;
 use crate::mbc::mbc0::Mbc0;
 use crate::mbc::mbc1::Mbc1;
 use crate::mbc::mbc2::Mbc2;
+use crate::mbc::mbc3::Mbc3;
 use crate::mbc::mbc5::Mbc5;
 use std::io::Cursor;
+use crate::state::SaveState;
+use crate::mbc::header::{CartridgeHeader, CartridgeType, HEADER_END};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900; //0x0100-0x01FF is skipped to show the cartridge header
+
+#[derive(Debug)]
+pub enum RomError {
+  Io(io::Error),
+  Empty,
+  Truncated(usize), //the image is too short to contain a cartridge header
+  InvalidArchive(ZipError),
+  NoRomInArchive,
+  UnsupportedMapper(u8, &'static str),
+  InvalidBootRom(usize) //boot roms are 256 bytes on the DMG and 2304 bytes on the CGB
+}
+
+impl fmt::Display for RomError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      RomError::Io(error) => write!(f, "failed to read the rom: {}", error),
+      RomError::Empty => write!(f, "the rom is empty"),
+      RomError::Truncated(size) => write!(f, "the rom is truncated - {} bytes are too short for a cartridge header", size),
+      RomError::InvalidArchive(error) => write!(f, "failed to read the zip archive: {}", error),
+      RomError::NoRomInArchive => write!(f, "no rom found in the zip archive"),
+      RomError::UnsupportedMapper(code, name) => write!(f, "unsupported cartridge type {} ({:#04X})", name, code),
+      RomError::InvalidBootRom(size) => write!(f, "a boot rom has {} or {} bytes and not {}", DMG_BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE, size)
+    }
+  }
+}
+
+impl std::error::Error for RomError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      RomError::Io(error) => Some(error),
+      RomError::InvalidArchive(error) => Some(error),
+      _ => None
+    }
+  }
+}
 
+impl From<io::Error> for RomError {
+  fn from(error: io::Error) -> RomError {
+    RomError::Io(error)
+  }
+}
 
-const ADDR_TITLE_START: u16 = 0x0134;
-const TITLE_SIZE: u16 = 16;
-const ADDR_CARTRIDGE_TYPE: usize = 0x0147;
+impl From<ZipError> for RomError {
+  fn from(error: ZipError) -> RomError {
+    match error {
+      ZipError::Io(error) => RomError::Io(error),
+      error => RomError::InvalidArchive(error)
+    }
+  }
+}
 
-pub trait Mbc : Send {
+pub trait Mbc : Send + SaveState {
   fn read_rom(&self, address: u16) -> u8;
   fn read_ram(&self, address: u16) -> u8;
   fn write_rom(&mut self, address: u16, value: u8);
   fn write_ram(&mut self, address: u16, value: u8);
+  fn do_ticks(&mut self, _ticks: usize) {} //for cartridge hardware that runs on the cpu clock like the rtc
+  fn save_data(&self) -> Option<Vec<u8>> { None } //cartridge state that survives a power cycle
+  fn save_data_changed(&mut self) -> bool { false } //true once after the data that survives a power cycle was written to
+  fn load_save_data(&mut self, _data: &[u8]) {}
+  fn header(&self) -> &CartridgeHeader;
   fn name(&self) -> String {
-    let mut name = String::with_capacity(TITLE_SIZE as usize);
+    self.header().title.clone()
+  }
+  fn checksum(&self) -> u32 { //header checksum and global checksum - together with the name good enough to identify a game
+    (self.header().header_checksum as u32) << 16 | self.header().global_checksum as u32
+  }
+}
 
-    for i in 0..TITLE_SIZE {
-      match self.read_rom(ADDR_TITLE_START + i ) {
-        0 => break,
-        ch => name.push(ch as char)
-      }
-    }
+pub fn load_rom(file_name: &str) -> Result<Box<dyn Mbc+'static>, RomError> {
+  let mut buffer = fs::read(file_name)?;
 
-    name
+  if file_name.ends_with(".zip") {
+    buffer = extract_rom_from_zip(buffer)?;
   }
+
+  load_rom_from_buffer(buffer)
 }
 
-pub fn load_rom(file_name: &str) -> Box<dyn Mbc+'static> {
-  let mut buffer = vec![];
-  let mut f = File::open(file_name).unwrap();
-  f.read_to_end(&mut buffer).expect("Error reading ROM!");
+pub fn load_rom_from_buffer(buffer: Vec<u8>) -> Result<Box<dyn Mbc+'static>, RomError> {
+  if buffer.is_empty() {
+    return Err(RomError::Empty)
+  }
+  if buffer.len() < HEADER_END {
+    return Err(RomError::Truncated(buffer.len()))
+  }
 
-  if file_name.ends_with(".zip") {
-    buffer = extract_rom_from_zip(buffer).unwrap();
+  let header = CartridgeHeader::parse(&buffer);
+  let rom = pad_rom(buffer, &header);
+
+  match header.cartridge_type {
+    CartridgeType::RomOnly => Ok(Box::new(Mbc0::new(rom, header))),
+    CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery => Ok(Box::new(Mbc1::new(rom, header))),
+    CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => Ok(Box::new(Mbc2::new(rom, header))),
+    CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery | CartridgeType::Mbc3 |
+    CartridgeType::Mbc3Ram | CartridgeType::Mbc3RamBattery => Ok(Box::new(Mbc3::new(rom, header))),
+    CartridgeType::Mbc5 | CartridgeType::Mbc5Ram | CartridgeType::Mbc5RamBattery | CartridgeType::Mbc5Rumble |
+    CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery => Ok(Box::new(Mbc5::new(rom, header))),
+    t => Err(RomError::UnsupportedMapper(header.cartridge_type_code, t.name()))
+  }
+}
+
+//problems the rom still loads with - homebrew and patched roms often have a wrong header checksum
+#[derive(Debug, PartialEq)]
+pub enum HeaderWarning {
+  HeaderChecksumMismatch { expected: u8, calculated: u8 } //the boot rom locks up on these cartridges but the game runs without it
+}
+
+impl fmt::Display for HeaderWarning {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      HeaderWarning::HeaderChecksumMismatch { expected, calculated } =>
+        write!(f, "header checksum mismatch - the header says {:#04X} but the rom sums up to {:#04X}", expected, calculated)
+    }
   }
+}
 
-  match buffer[ADDR_CARTRIDGE_TYPE] {
-    0x00 => Box::new(Mbc0::new(buffer)),
-    0x01..=0x03 => Box::new(Mbc1::new(buffer)),
-    0x05..=0x06 => Box::new(Mbc2::new(buffer)),
-    //0x0F..=0x13 => "MBC3",
-    0x19..=0x1E => Box::new(Mbc5::new(buffer)),
-    v => panic!("Unsupported cartridge type {:#02X}", v)
+pub fn header_warning(header: &CartridgeHeader) -> Option<HeaderWarning> {
+  if header.header_checksum_valid {
+    None
+  } else {
+    Some(HeaderWarning::HeaderChecksumMismatch { expected: header.header_checksum, calculated: header.calculated_header_checksum })
   }
 }
 
-fn extract_rom_from_zip(buffer: Vec<u8>) -> Result<Vec<u8>, &'static str> {
-  let mut archive = ZipArchive::new(Cursor::new(buffer)).expect("Failed to read ZIP file!");
+pub fn load_boot_rom(file_name: &str) -> Result<Vec<u8>, RomError> {
+  let buffer = fs::read(file_name)?;
+
+  match buffer.len() {
+    DMG_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE => Ok(buffer),
+    size => Err(RomError::InvalidBootRom(size))
+  }
+}
+
+//fills the rom up to the declared size so every bank the mbc can select exists - bad dumps are often too short
+fn pad_rom(mut buffer: Vec<u8>, header: &CartridgeHeader) -> Vec<u8> {
+  let banks = header.rom_size.max(buffer.len()).div_ceil(ROM_BANK_SIZE);
+  buffer.resize(banks.max(2) * ROM_BANK_SIZE, 0xFF);
+  buffer
+}
+
+fn extract_rom_from_zip(buffer: Vec<u8>) -> Result<Vec<u8>, RomError> {
+  let mut archive = ZipArchive::new(Cursor::new(buffer))?;
 
   for i in 0..archive.len() {
-    let mut file = archive.by_index(i).unwrap();
-    if file.name().ends_with(".gb") {
+    let mut file = archive.by_index(i)?;
+    if file.name().ends_with(".gb") || file.name().ends_with(".gbc") {
       let mut result = vec![];
-      file.read_to_end(&mut result).expect("Failed to extract rom from zip file!");
+      file.read_to_end(&mut result)?;
       return Ok(result)
     }
   }
-  Err("No rom found in zip file!")
+  Err(RomError::NoRomInArchive)
 }
 
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn short_images_are_rejected()
+  {
+    assert!(matches!(load_rom_from_buffer(vec![]), Err(RomError::Empty)));
+    assert!(matches!(load_rom_from_buffer(vec![0; 0x100]), Err(RomError::Truncated(0x100))));
+  }
+
+  #[test]
+  fn header_problems_are_reported()
+  {
+    let mut rom = vec![0; 0x8000];
+    rom[0x014D] = 0x42;
+    let cartridge = load_rom_from_buffer(rom.clone()).unwrap(); //a wrong checksum is only a warning
+    assert_eq!(header_warning(cartridge.header()), Some(HeaderWarning::HeaderChecksumMismatch { expected: 0x42, calculated: 0xE7 }));
+
+    rom[0x0147] = 0x20; //MBC6
+    assert!(matches!(load_rom_from_buffer(rom), Err(RomError::UnsupportedMapper(0x20, "MBC6"))));
+  }
+}
@@ -4,14 +4,32 @@ use crate::ppu::VOAM_SIZE;
 use crate::ppu::Ppu;
 use crate::apu::Apu;
 
-use crate::serial::Serial;
-use std::sync::mpsc::Sender;
+use crate::serial::{Serial, SerialSink};
+use crate::link::LinkCable;
 use crate::mbc::Mbc;
+use crate::model::HardwareModel;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
 
-const WRAM_SIZE: usize = 0x8000;
+const WRAM_SIZE: usize = 0x8000; //8 banks of 4kB - the DMG only uses the first two
+const WRAM_BANK_SIZE: usize = 0x1000;
 const HRAM_SIZE: usize = 0x7F;
+const HDMA_BLOCK_SIZE: u16 = 0x10;
+const HDMA_BLOCK_TICKS: usize = 32; //the cpu is stopped for 8 M-cycles per copied block
+const OAM_DMA_STARTUP: usize = 2; //M-cycles from the write to FF46 until the first byte is copied
+const OAM_DMA_CYCLE_TICKS: usize = 4; //one byte per M-cycle
+
+//the cpu and an OAM DMA collide when they use the same bus at the same time
+#[derive(PartialEq)]
+enum Bus {
+  External, //cartridge and on the DMG work ram
+  Video,
+  WorkRam, //the CGB has a separate bus for its work ram
+  Internal //OAM, io registers and HRAM
+}
 
 pub struct Mmu {
+  boot_rom: Vec<u8>,
+  boot_rom_mapped: bool, //the boot rom covers the cartridge rom until FF50 is written
   wram: [u8; WRAM_SIZE],
   hram: [u8; HRAM_SIZE],
   ppu: Ppu,
@@ -23,82 +41,348 @@ pub struct Mmu {
   interrupt_enable: u8,
   interrupt_request: u8,
   voam_oam: u8,
+  oam_dma_source: u16,
+  oam_dma_index: usize, //the next byte the OAM DMA copies - VOAM_SIZE when no transfer is running
+  oam_dma_clock: usize,
+  oam_dma_value: u8, //the byte on the bus of the OAM DMA
+  oam_dma_start: usize, //M-cycles until a requested transfer replaces the running one - 0 without a request
+  oam_dma_next_source: u16,
+  model: HardwareModel,
+  cgb_mode: bool,
+  wram_bank: usize, //SVBK FF70 - the bank mapped to D000-DFFF
+  double_speed: bool, //KEY1 FF4D bit 7
+  speed_switch_armed: bool, //KEY1 FF4D bit 0 - the next STOP switches the speed
+  hdma_source: u16, //HDMA1 FF51 HDMA2 FF52
+  hdma_destination: u16, //HDMA3 FF53 HDMA4 FF54 - offset into vram
+  hdma_active: bool, //an hblank dma is running
+  hdma_blocks: usize, //blocks the hblank dma still has to copy - kept when it is stopped
+  dma_stall: usize, //ticks the cpu has to wait for a dma to finish
 }
 
 impl Mmu {
-  pub fn new(rom: Box<dyn Mbc>, video_sender: Sender<Vec<u8>>, audio_sender: Sender<Vec<i16>>) -> Mmu {
-    Mmu {
+  pub fn new(rom: Box<dyn Mbc>, boot_rom: Option<Vec<u8>>, model: HardwareModel) -> Mmu {
+    let cgb_mode = model.cgb_mode(rom.header());
+    let boot = boot_rom.is_none();
+
+    let mut mmu = Mmu {
+      boot_rom_mapped: boot_rom.is_some(),
+      boot_rom: boot_rom.unwrap_or_default(),
       wram: [0; WRAM_SIZE],
       hram: [0; HRAM_SIZE],
-      ppu: Ppu::new(video_sender),
-      apu: Apu::new(audio_sender),
+      ppu: Ppu::new(cgb_mode, model),
+      apu: Apu::new(model),
       timer: Timer::new(),
       joypad: Joypad::new(),
       mbc: rom,
-      serial: Serial::new(),
+      serial: Serial::new(cgb_mode),
       interrupt_enable: 0x00,
       interrupt_request: 0x00,
       voam_oam: 0x00,
+      oam_dma_source: 0x0000,
+      oam_dma_index: VOAM_SIZE,
+      oam_dma_clock: 0,
+      oam_dma_value: 0xFF,
+      oam_dma_start: 0,
+      oam_dma_next_source: 0x0000,
+      model,
+      cgb_mode,
+      wram_bank: 1,
+      double_speed: false,
+      speed_switch_armed: false,
+      hdma_source: 0x0000,
+      hdma_destination: 0x0000,
+      hdma_active: false,
+      hdma_blocks: 0,
+      dma_stall: 0,
+    };
+
+    if boot { //skip the boot rom and start with the state it leaves behind
+      mmu.post_boot(model);
     }
+
+    mmu
   }
 
+  //the io registers and the ppu state the boot rom of the model leaves behind when it jumps to 0x0100
+  fn post_boot(&mut self, model: HardwareModel) {
+    let registers = match model {
+      HardwareModel::DMG0 | HardwareModel::DMG | HardwareModel::MGB => &DMG_REGISTERS,
+      HardwareModel::SGB | HardwareModel::SGB2 => &SGB_REGISTERS,
+      HardwareModel::CGB | HardwareModel::AGB => &CGB_REGISTERS
+    };
+    for &(address, value) in registers.iter() {
+      match address {
+        0xFF46 => self.voam_oam = value,
+        _ => self.write_byte(address, value)
+      }
+    }
+
+    if !matches!(model, HardwareModel::SGB | HardwareModel::SGB2) { //the SGB plays its boot sound on the SNES
+      self.apu.finish_boot_sound();
+    }
+    self.ppu.post_boot(model);
+    self.interrupt_request = 0x01; //the vblank of the last boot rom frame is still pending
+    self.timer.set_divider(model.post_boot_divider());
+  }
+
+  /*
+    while an OAM DMA is running the cpu can't access OAM - reads return 0xFF and writes are ignored.
+    accesses to the bus the DMA reads from see the byte the DMA transfers, only the other buses and FF00-FFFF work as usual.
+    the ppu locks the cpu out of vram, OAM and the CGB palettes depending on its mode.
+  */
   pub fn read_byte(&self, address: u16) -> u8 {
+    if self.ppu.blocks_cpu(address) {
+      return 0xFF
+    }
+    if self.oam_dma_active() {
+      let bus = self.bus(address);
+      if bus == Bus::Internal && address < 0xFF00 {
+        return 0xFF
+      }
+      if bus == self.bus(self.oam_dma_source) {
+        return self.oam_dma_value
+      }
+    }
+    self.read_memory(address)
+  }
+
+  pub fn write_byte(&mut self, address: u16, value: u8) {
+    if self.ppu.blocks_cpu(address) {
+      return
+    }
+    if self.oam_dma_active() {
+      let bus = self.bus(address);
+      if (bus == Bus::Internal && address < 0xFF00) || bus == self.bus(self.oam_dma_source) {
+        return
+      }
+    }
+    self.write_memory(address, value);
+  }
+
+  //reads without the restrictions of the cpu - used by the DMA units
+  fn read_memory(&self, address: u16) -> u8 {
     match address {
+      0x0000 ..= 0x00FF | 0x0200 ..= 0x08FF if self.boot_rom_mapped && (address as usize) < self.boot_rom.len() => self.boot_rom[address as usize],
       0x0000 ..= 0x7FFF => self.mbc.read_rom(address), //ROM from cartridge
       0x8000 ..= 0x9FFF => self.ppu.read_byte(address), //VRAM
       0xA000 ..= 0xBFFF => self.mbc.read_ram(address - 0xA000),
-      0xC000 ..= 0xDFFF => self.wram[address as usize - 0xC000], //WRAM
-      0xE000 ..= 0xFDFF => self.wram[address as usize - 0xE000], //WRAM Echo
+      0xC000 ..= 0xDFFF => self.wram[self.wram_address(address - 0xC000)], //WRAM
+      0xE000 ..= 0xFDFF => self.wram[self.wram_address(address - 0xE000)], //WRAM Echo
       0xFE00 ..= 0xFE9F => self.ppu.read_byte(address), //OAM
       0xFEA0 ..= 0xFEFF => 0, //not useable
       0xFF00 => self.joypad.read(), //Joypad
       0xFF01 ..= 0xFF02 => self.serial.read(address), //serial
       0xFF04 ..= 0xFF07 => self.timer.read_byte(address), //TIMER
-      0xFF0F => self.interrupt_request,
+      0xFF0F => 0xE0 | self.interrupt_request, //the upper bits are unused and read 1
       0xFF10 ..= 0xFF3F => self.apu.read_byte(address), //sound
       0xFF46 => self.voam_oam,
       0xFF40 ..= 0xFF4B => self.ppu.read_byte(address),
+      0xFF50 => 0xFF,
+      0xFF4D if self.cgb_mode => 0x7E | (if self.double_speed { 0x80 } else { 0x00 }) | (if self.speed_switch_armed { 0x01 } else { 0x00 }),
+      0xFF4F | 0xFF68 ..= 0xFF6B if self.cgb_mode => self.ppu.read_byte(address), //VBK and color palettes
+      0xFF55 if self.cgb_mode => match (self.hdma_active, self.hdma_blocks) {
+        (_, 0) => 0xFF, //finished
+        (true, blocks) => (blocks - 1) as u8,
+        (false, blocks) => 0x80 | (blocks - 1) as u8 //stopped - games read the remaining length to resume the transfer
+      },
+      0xFF70 if self.cgb_mode => 0xF8 | self.wram_bank as u8,
       0xFF80 ..= 0xFFFE => self.hram[address as usize - 0xFF80], //HRAM
       0xFFFF => self.interrupt_enable,
       _ => 0
     }
   }
 
-  pub fn read_word(&self, address: u16) -> u16 { //LSB FIRST
-    self.read_byte(address) as u16 | (self.read_byte(address + 1) as u16) << 8
-  }
-
-  pub fn write_byte(&mut self, address: u16, value: u8) {
+  fn write_memory(&mut self, address: u16, value: u8) {
     match address {
       0x0000 ..= 0x7FFF => self.mbc.write_rom(address, value), //ROM cartridge
       0x8000 ..= 0x9FFF => self.ppu.write_byte(address, value), //VRAM
       0xA000 ..= 0xBFFF => self.mbc.write_ram(address - 0xA000, value),
-      0xC000 ..= 0xDFFF => self.wram[address as usize - 0xC000] = value, //WRAM
-      0xE000 ..= 0xFDFF => self.wram[address as usize - 0xE000] = value, //WRAM Echo
+      0xC000 ..= 0xDFFF => { let address = self.wram_address(address - 0xC000); self.wram[address] = value }, //WRAM
+      0xE000 ..= 0xFDFF => { let address = self.wram_address(address - 0xE000); self.wram[address] = value }, //WRAM Echo
       0xFE00 ..= 0xFE9F => self.ppu.write_byte(address, value), //OAM
       0xFEA0 ..= 0xFEFF => (), //not useable
       0xFF00 => self.joypad.write(value), //JOYPAD
       0xFF01 ..= 0xFF02 => self.serial.write(address, value), //serial
-      0xFF04 ..= 0xFF07 => self.timer.write_byte(address, value), //timer
-      0xFF0F => self.interrupt_request = value,
+      0xFF04 => { //resetting DIV lets the frame sequencer bit fall
+        if self.timer.divider() & self.frame_sequencer_bit() != 0 {
+          self.apu.step_frame_sequencer();
+        }
+        self.timer.write_byte(address, value);
+      },
+      0xFF05 ..= 0xFF07 => self.timer.write_byte(address, value), //timer
+      0xFF0F => self.interrupt_request = value & 0x1F,
       0xFF10 ..= 0xFF3F => self.apu.write_byte(address, value), //sound
-      0xFF46 => { self.voam_oam = value; self.copy_to_voam(value) }, //it's in front to capture it before it reaches the next line
+      0xFF46 => self.request_oam_dma(value), //it's in front to capture it before it reaches the next line
       0xFF40 ..= 0xFF4B => self.ppu.write_byte(address, value),
+      0xFF50 => self.boot_rom_mapped = false, //the last instruction of the boot rom - there is no way back
+      0xFF4D if self.cgb_mode => self.speed_switch_armed = value & 0x01 == 0x01,
+      0xFF4F | 0xFF68 ..= 0xFF6B if self.cgb_mode => self.ppu.write_byte(address, value), //VBK and color palettes
+      0xFF51 if self.cgb_mode => self.hdma_source = (self.hdma_source & 0x00FF) | (value as u16) << 8,
+      0xFF52 if self.cgb_mode => self.hdma_source = (self.hdma_source & 0xFF00) | (value & 0xF0) as u16,
+      0xFF53 if self.cgb_mode => self.hdma_destination = (self.hdma_destination & 0x00FF) | ((value & 0x1F) as u16) << 8,
+      0xFF54 if self.cgb_mode => self.hdma_destination = (self.hdma_destination & 0xFF00) | (value & 0xF0) as u16,
+      0xFF55 if self.cgb_mode => self.start_hdma(value),
+      0xFF70 if self.cgb_mode => self.wram_bank = match value & 0x07 { 0 => 1, n => n as usize },
       0xFF80 ..= 0xFFFE => self.hram[address as usize - 0xFF80] = value, //HRAM
       0xFFFF => self.interrupt_enable = value,
       _ => {}
     }
   }
 
-  pub fn write_word(&mut self, address: u16, value: u16) {
-    self.write_byte(address, (value & 0x00FF) as u8); //LSB first
-    self.write_byte(address + 1, ((value & 0xFF00) >> 8) as u8);
-  }
+  //returns the ticks at normal speed - in double speed mode the ppu, the apu and the rtc only see half of the cpu ticks
+  pub fn do_ticks(&mut self, ticks: usize) -> usize {
+    let lcd_ticks = if self.double_speed { ticks / 2 } else { ticks };
 
-  pub fn do_ticks(&mut self, ticks: usize) {
+    let divider = self.timer.divider() as usize;
     self.timer.do_ticks(ticks);
-    self.ppu.do_ticks(ticks);
-    self.apu.do_ticks(ticks);
+    let period = self.frame_sequencer_bit() as usize * 2;
+    for _ in divider / period .. (divider + ticks) / period { //every time the bit falls
+      self.apu.step_frame_sequencer();
+    }
+    self.serial.do_ticks(ticks);
+    self.do_oam_dma(ticks);
+    self.ppu.do_ticks(lcd_ticks);
+    self.apu.do_ticks(lcd_ticks);
+    self.mbc.do_ticks(lcd_ticks);
+
+    if self.ppu.hblank_started {
+      self.ppu.hblank_started = false;
+      if self.hdma_active {
+        self.copy_hdma_block();
+        self.hdma_blocks -= 1;
+        self.hdma_active = self.hdma_blocks > 0;
+      }
+    }
+
+    self.process_irq_requests(); //the next access within the instruction already sees the new requests in IF
+    lcd_ticks
+  }
+
+  //the apu frame sequencer is clocked when DIV bit 4 falls - bit 5 in double speed to keep it at 512hz
+  fn frame_sequencer_bit(&self) -> u16 {
+    if self.double_speed { 0x2000 } else { 0x1000 }
+  }
+
+  //true if the ppu keeps the cpu from accessing the address in its current mode
+  pub fn blocked_by_ppu(&self, address: u16) -> bool {
+    self.ppu.blocks_cpu(address)
+  }
+
+  //the ticks the cpu is stopped by a dma since the last call
+  pub fn take_dma_stall(&mut self) -> usize {
+    let stall = self.dma_stall;
+    self.dma_stall = 0;
+    stall
+  }
+
+  //STOP switches the speed if it was armed through KEY1 - returns false if it is a regular STOP
+  pub fn switch_speed(&mut self) -> bool {
+    if self.cgb_mode && self.speed_switch_armed {
+      self.double_speed = !self.double_speed;
+      self.speed_switch_armed = false;
+      true
+    } else {
+      false
+    }
+  }
+
+  //C000-CFFF is always bank 0 - D000-DFFF the bank selected by SVBK
+  fn wram_address(&self, offset: u16) -> usize {
+    let offset = offset as usize;
+    if offset < WRAM_BANK_SIZE {
+      offset
+    } else {
+      self.wram_bank * WRAM_BANK_SIZE + offset - WRAM_BANK_SIZE
+    }
+  }
+
+  /*
+    HDMA5 FF55
+      Bit 7   0 = general purpose dma copies everything at once, 1 = hblank dma copies one block per hblank
+      Bit 6-0 number of 16 byte blocks - 1
+    writing bit 7 = 0 while an hblank dma is running stops it - reading returns the remaining blocks - 1 with bit 7 set
+  */
+  fn start_hdma(&mut self, value: u8) {
+    if self.hdma_active && value & 0x80 == 0x00 {
+      self.hdma_active = false;
+      return
+    }
+
+    let blocks = (value & 0x7F) as usize + 1;
+    if value & 0x80 == 0x80 {
+      self.hdma_active = true;
+      self.hdma_blocks = blocks;
+    } else {
+      for _ in 0..blocks {
+        self.copy_hdma_block();
+      }
+      self.hdma_blocks = 0;
+    }
+  }
+
+  fn copy_hdma_block(&mut self) {
+    for offset in 0..HDMA_BLOCK_SIZE {
+      let value = self.read_memory(self.hdma_source.wrapping_add(offset));
+      self.ppu.write_byte(0x8000 | ((self.hdma_destination + offset) & 0x1FFF), value);
+    }
+    self.hdma_source = self.hdma_source.wrapping_add(HDMA_BLOCK_SIZE);
+    self.hdma_destination = (self.hdma_destination + HDMA_BLOCK_SIZE) & 0x1FFF;
+    self.dma_stall += if self.double_speed { HDMA_BLOCK_TICKS * 2 } else { HDMA_BLOCK_TICKS };
+  }
+
+  pub fn get_frame_buffer(&self) -> &[u16] {
+    self.ppu.get_frame_buffer()
+  }
+
+  pub fn get_audio_buffer(&self) -> &[i16] {
+    self.apu.get_audio_buffer()
+  }
+
+  //returns true once per finished frame
+  pub fn take_frame_ready(&mut self) -> bool {
+    let frame_ready = self.ppu.frame_ready;
+    self.ppu.frame_ready = false;
+    frame_ready
+  }
+
+  pub fn clear_audio_buffer(&mut self) {
+    self.apu.clear_audio_buffer();
+  }
+
+  pub fn save_data(&self) -> Option<Vec<u8>> {
+    self.mbc.save_data()
+  }
+
+  pub fn save_data_changed(&mut self) -> bool {
+    self.mbc.save_data_changed()
+  }
+
+  pub fn set_serial_sink(&mut self, sink: Option<Box<dyn SerialSink>>) {
+    self.serial.set_sink(sink);
+  }
+
+  pub fn set_link_cable(&mut self, link: Option<Box<dyn LinkCable>>) {
+    self.serial.set_link(link);
+  }
+
+  pub fn set_sample_rate(&mut self, sample_rate: usize) {
+    self.apu.set_sample_rate(sample_rate);
+  }
+
+  pub fn set_pixel_fifo(&mut self, pixel_fifo: bool) {
+    self.ppu.set_pixel_fifo(pixel_fifo);
+  }
+
+  pub fn rom_name(&self) -> String {
+    self.mbc.name()
+  }
+
+  pub fn rom_checksum(&self) -> u32 {
+    self.mbc.checksum()
+  }
+
+  pub fn model(&self) -> HardwareModel {
+    self.model
   }
 
   pub fn process_irq_requests(&mut self) {
@@ -117,47 +401,383 @@ impl Mmu {
       self.timer.irq_timer = false;
     }
 
-    //@TODO add joypad and serial interrupts
+    if self.serial.irq_serial {
+      self.interrupt_request |= 0x08;
+      self.serial.irq_serial = false;
+    }
+
+    if self.joypad.irq_joypad {
+      self.interrupt_request |= 0x10;
+      self.joypad.irq_joypad = false;
+    }
+  }
+
+  /*
+    DMA FF46 - copies 160 bytes from XX00-XX9F to OAM with one byte per M-cycle after a short startup
+    a write while a transfer is running restarts it with the new source - the old transfer goes on until the new one starts
+    sources above DFFF read the work ram like the echo area
+  */
+  fn request_oam_dma(&mut self, value: u8) {
+    self.voam_oam = value;
+    self.oam_dma_next_source = oam_dma_source(value);
+    self.oam_dma_start = OAM_DMA_STARTUP;
   }
 
-  fn copy_to_voam(&mut self, value: u8) {
-    let mem_start = (value as u16) << 8;
-    for offset in 0..VOAM_SIZE {
-      self.write_byte(0xFE00 + offset as u16, self.read_byte(mem_start + offset as u16));
+  fn do_oam_dma(&mut self, ticks: usize) {
+    if !self.oam_dma_active() && self.oam_dma_start == 0 {
+      return
+    }
+
+    self.oam_dma_clock += ticks;
+    while self.oam_dma_clock >= OAM_DMA_CYCLE_TICKS {
+      self.oam_dma_clock -= OAM_DMA_CYCLE_TICKS;
+
+      if self.oam_dma_start > 0 {
+        self.oam_dma_start -= 1;
+        if self.oam_dma_start == 0 {
+          self.oam_dma_source = self.oam_dma_next_source;
+          self.oam_dma_index = 0;
+        }
+      }
+
+      if self.oam_dma_active() {
+        let offset = self.oam_dma_index as u16;
+        self.oam_dma_value = self.read_memory(self.oam_dma_source + offset);
+        self.ppu.write_byte(0xFE00 + offset, self.oam_dma_value);
+        self.oam_dma_index += 1;
+      }
+    }
+
+    if !self.oam_dma_active() && self.oam_dma_start == 0 {
+      self.oam_dma_clock = 0;
+    }
+  }
+
+  fn oam_dma_active(&self) -> bool {
+    self.oam_dma_index < VOAM_SIZE
+  }
+
+  fn bus(&self, address: u16) -> Bus {
+    match address {
+      0x8000 ..= 0x9FFF => Bus::Video,
+      0xC000 ..= 0xFDFF if self.cgb_mode => Bus::WorkRam,
+      0xFE00 ..= 0xFFFF => Bus::Internal,
+      _ => Bus::External
     }
   }
 }
 
 /*
-    buffer[0xFF05] = 0x00; // TIMA
-    buffer[0xFF06] = 0x00; // TMA
-    buffer[0xFF07] = 0x00; // TAC
-    buffer[0xFF10] = 0x80; // NR10
-    buffer[0xFF11] = 0xBF; // NR11
-    buffer[0xFF12] = 0xF3; // NR12
-    buffer[0xFF14] = 0xBF; // NR14
-    buffer[0xFF16] = 0x3F; // NR21
-    buffer[0xFF17] = 0x00; // NR22
-    buffer[0xFF19] = 0xBF; // NR24
-    buffer[0xFF1A] = 0x7F; // NR30
-    buffer[0xFF1B] = 0xFF; // NR31
-    buffer[0xFF1C] = 0x9F; // NR32
-    buffer[0xFF1E] = 0xBF; // NR33
-    buffer[0xFF20] = 0xFF; // NR41
-    buffer[0xFF21] = 0x00; // NR42
-    buffer[0xFF22] = 0x00; // NR43
-    buffer[0xFF23] = 0xBF; // NR30
-    buffer[0xFF24] = 0x77; // NR50
-    buffer[0xFF25] = 0xF3; // NR51
-    buffer[0xFF26] = 0xF1; //-GB, 0xF0-SGB // NR52
-    buffer[0xFF40] = 0x91; // LCDC
-    buffer[0xFF42] = 0x00; // SCY
-    buffer[0xFF43] = 0x00; // SCx
-    buffer[0xFF45] = 0x00; // LYC
-    buffer[0xFF47] = 0xFC; // BGP
-    buffer[0xFF48] = 0xFF; // OBP0
-    buffer[0xFF49] = 0xFF; // OBP1
-    buffer[0xFF4A] = 0x00; // WY
-    buffer[0xFF4B] = 0x00; // Wx
-    buffer[0xFFFF] = 0x00; // IE
-*/
\ No newline at end of file
+  the io registers each boot rom leaves behind - the trigger bits of NRx4 are left out, the boot sound has already finished.
+  the SGB boot rom leaves the sound off, the CGB one sets the serial clock to internal and fast and leaves 0 in DMA.
+*/
+const DMG_REGISTERS: [(u16, u8); 33] = [ //DMG0, DMG and MGB
+  (0xFF26, 0xF1), //NR52 - the sound has to be on before the other registers can be written
+  (0xFF02, 0x00), //SC
+  (0xFF05, 0x00), //TIMA
+  (0xFF06, 0x00), //TMA
+  (0xFF07, 0x00), //TAC
+  (0xFF10, 0x80), //NR10
+  (0xFF11, 0xBF), //NR11
+  (0xFF12, 0xF3), //NR12
+  (0xFF13, 0xFF), //NR13
+  (0xFF14, 0x3F), //NR14
+  (0xFF16, 0x3F), //NR21
+  (0xFF17, 0x00), //NR22
+  (0xFF19, 0x3F), //NR24
+  (0xFF1A, 0x7F), //NR30
+  (0xFF1B, 0xFF), //NR31
+  (0xFF1C, 0x9F), //NR32
+  (0xFF1E, 0x3F), //NR34
+  (0xFF20, 0xFF), //NR41
+  (0xFF21, 0x00), //NR42
+  (0xFF22, 0x00), //NR43
+  (0xFF23, 0x3F), //NR44
+  (0xFF24, 0x77), //NR50
+  (0xFF25, 0xF3), //NR51
+  (0xFF40, 0x91), //LCDC - lcd, background and tile data at 0x8000 on
+  (0xFF42, 0x00), //SCY
+  (0xFF43, 0x00), //SCX
+  (0xFF45, 0x00), //LYC
+  (0xFF46, 0xFF), //DMA - only the register, no transfer is started
+  (0xFF47, 0xFC), //BGP
+  (0xFF48, 0xFF), //OBP0
+  (0xFF49, 0xFF), //OBP1
+  (0xFF4A, 0x00), //WY
+  (0xFF4B, 0x00) //WX
+];
+
+const SGB_REGISTERS: [(u16, u8); 33] = [ //SGB and SGB2
+  (0xFF26, 0xF0), //NR52 - the sound has to be on before the other registers can be written
+  (0xFF02, 0x00), //SC
+  (0xFF05, 0x00), //TIMA
+  (0xFF06, 0x00), //TMA
+  (0xFF07, 0x00), //TAC
+  (0xFF10, 0x80), //NR10
+  (0xFF11, 0xBF), //NR11
+  (0xFF12, 0xF3), //NR12
+  (0xFF13, 0xFF), //NR13
+  (0xFF14, 0x3F), //NR14
+  (0xFF16, 0x3F), //NR21
+  (0xFF17, 0x00), //NR22
+  (0xFF19, 0x3F), //NR24
+  (0xFF1A, 0x7F), //NR30
+  (0xFF1B, 0xFF), //NR31
+  (0xFF1C, 0x9F), //NR32
+  (0xFF1E, 0x3F), //NR34
+  (0xFF20, 0xFF), //NR41
+  (0xFF21, 0x00), //NR42
+  (0xFF22, 0x00), //NR43
+  (0xFF23, 0x3F), //NR44
+  (0xFF24, 0x77), //NR50
+  (0xFF25, 0xF3), //NR51
+  (0xFF40, 0x91), //LCDC - lcd, background and tile data at 0x8000 on
+  (0xFF42, 0x00), //SCY
+  (0xFF43, 0x00), //SCX
+  (0xFF45, 0x00), //LYC
+  (0xFF46, 0xFF), //DMA - only the register, no transfer is started
+  (0xFF47, 0xFC), //BGP
+  (0xFF48, 0xFF), //OBP0
+  (0xFF49, 0xFF), //OBP1
+  (0xFF4A, 0x00), //WY
+  (0xFF4B, 0x00) //WX
+];
+
+const CGB_REGISTERS: [(u16, u8); 33] = [ //CGB and AGB
+  (0xFF26, 0xF1), //NR52 - the sound has to be on before the other registers can be written
+  (0xFF02, 0x03), //SC
+  (0xFF05, 0x00), //TIMA
+  (0xFF06, 0x00), //TMA
+  (0xFF07, 0x00), //TAC
+  (0xFF10, 0x80), //NR10
+  (0xFF11, 0xBF), //NR11
+  (0xFF12, 0xF3), //NR12
+  (0xFF13, 0xFF), //NR13
+  (0xFF14, 0x3F), //NR14
+  (0xFF16, 0x3F), //NR21
+  (0xFF17, 0x00), //NR22
+  (0xFF19, 0x3F), //NR24
+  (0xFF1A, 0x7F), //NR30
+  (0xFF1B, 0xFF), //NR31
+  (0xFF1C, 0x9F), //NR32
+  (0xFF1E, 0x3F), //NR34
+  (0xFF20, 0xFF), //NR41
+  (0xFF21, 0x00), //NR42
+  (0xFF22, 0x00), //NR43
+  (0xFF23, 0x3F), //NR44
+  (0xFF24, 0x77), //NR50
+  (0xFF25, 0xF3), //NR51
+  (0xFF40, 0x91), //LCDC - lcd, background and tile data at 0x8000 on
+  (0xFF42, 0x00), //SCY
+  (0xFF43, 0x00), //SCX
+  (0xFF45, 0x00), //LYC
+  (0xFF46, 0x00), //DMA - only the register, no transfer is started
+  (0xFF47, 0xFC), //BGP
+  (0xFF48, 0xFF), //OBP0
+  (0xFF49, 0xFF), //OBP1
+  (0xFF4A, 0x00), //WY
+  (0xFF4B, 0x00) //WX
+];
+
+fn oam_dma_source(value: u8) -> u16 {
+  let value = if value >= 0xE0 { value - 0x20 } else { value };
+  (value as u16) << 8
+}
+
+impl SaveState for Mmu {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.boot_rom_mapped);
+    writer.write_bytes(&self.wram);
+    writer.write_bytes(&self.hram);
+    writer.write_u8(self.interrupt_enable);
+    writer.write_u8(self.interrupt_request);
+    writer.write_u8(self.voam_oam);
+    writer.write_u16(self.oam_dma_source);
+    writer.write_usize(self.oam_dma_index);
+    writer.write_usize(self.oam_dma_clock);
+    writer.write_u8(self.oam_dma_value);
+    writer.write_usize(self.oam_dma_start);
+    writer.write_u16(self.oam_dma_next_source);
+    writer.write_usize(self.wram_bank);
+    writer.write_bool(self.double_speed);
+    writer.write_bool(self.speed_switch_armed);
+    writer.write_u16(self.hdma_source);
+    writer.write_u16(self.hdma_destination);
+    writer.write_bool(self.hdma_active);
+    writer.write_usize(self.hdma_blocks);
+    writer.write_usize(self.dma_stall);
+    self.ppu.save_state(writer);
+    self.apu.save_state(writer);
+    self.timer.save_state(writer);
+    self.joypad.save_state(writer);
+    self.serial.save_state(writer);
+    self.mbc.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.boot_rom_mapped = reader.read_bool()? && !self.boot_rom.is_empty();
+    reader.read_bytes(&mut self.wram)?;
+    reader.read_bytes(&mut self.hram)?;
+    self.interrupt_enable = reader.read_u8()?;
+    self.interrupt_request = reader.read_u8()?;
+    self.voam_oam = reader.read_u8()?;
+    self.oam_dma_source = oam_dma_source((reader.read_u16()? >> 8) as u8);
+    self.oam_dma_index = reader.read_usize()?.min(VOAM_SIZE);
+    self.oam_dma_clock = reader.read_usize()?;
+    self.oam_dma_value = reader.read_u8()?;
+    self.oam_dma_start = reader.read_usize()?.min(OAM_DMA_STARTUP);
+    self.oam_dma_next_source = oam_dma_source((reader.read_u16()? >> 8) as u8);
+    self.wram_bank = match reader.read_usize()? & 0x07 { 0 => 1, n => n }; //bank 0 selects bank 1 like a write to SVBK
+    self.double_speed = reader.read_bool()?;
+    self.speed_switch_armed = reader.read_bool()?;
+    self.hdma_source = reader.read_u16()?;
+    self.hdma_destination = reader.read_u16()? & 0x1FFF;
+    self.hdma_active = reader.read_bool()?;
+    self.hdma_blocks = reader.read_usize()?.min(0x80);
+    self.hdma_active &= self.hdma_blocks > 0; //a running transfer has at least one block left
+    self.dma_stall = reader.read_usize()?;
+    self.ppu.load_state(reader)?;
+    self.apu.load_state(reader)?;
+    self.timer.load_state(reader)?;
+    self.joypad.load_state(reader)?;
+    self.serial.load_state(reader)?;
+    self.mbc.load_state(reader)
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use crate::mbc::load_rom_from_buffer;
+  use crate::mbc::header::CartridgeHeader;
+
+  fn mmu(boot_rom: Option<Vec<u8>>) -> Mmu {
+    let mut rom = vec![0x11; 0x8000];
+    rom[0x0147] = 0x00;
+    rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+    Mmu::new(load_rom_from_buffer(rom).unwrap(), boot_rom, HardwareModel::DMG)
+  }
+
+  #[test]
+  fn boot_rom_covers_the_cartridge_until_ff50_is_written()
+  {
+    let mut mmu = mmu(Some(vec![0x22; 0x100]));
+
+    assert_eq!(mmu.read_byte(0x0000), 0x22);
+    assert_eq!(mmu.read_byte(0x00FF), 0x22);
+    assert_eq!(mmu.read_byte(0x0100), 0x11);
+
+    mmu.write_byte(0xFF50, 0x01);
+    assert_eq!(mmu.read_byte(0x0000), 0x11);
+  }
+
+  #[test]
+  fn io_registers_after_the_boot_rom()
+  {
+    let rom = |model| {
+      let mut rom = vec![0x00; 0x8000];
+      rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+      Mmu::new(load_rom_from_buffer(rom).unwrap(), None, model)
+    };
+
+    let dmg = rom(HardwareModel::DMG);
+    assert_eq!(dmg.read_byte(0xFF26), 0xF1); //channel 1 is still on after the boot sound
+    assert_eq!(dmg.read_byte(0xFF44), 0x00);
+    assert_eq!(dmg.read_byte(0xFF41), 0x85);
+    assert_eq!(dmg.read_byte(0xFF46), 0xFF);
+
+    let dmg0 = rom(HardwareModel::DMG0);
+    assert_eq!(dmg0.read_byte(0xFF44), 0x91);
+    assert_eq!(dmg0.read_byte(0xFF41), 0x81);
+
+    assert_eq!(rom(HardwareModel::SGB).read_byte(0xFF26), 0xF0);
+
+    let mut cgb = rom(HardwareModel::CGB); //a DMG game on the CGB gets the compatibility palettes
+    assert_eq!(cgb.read_byte(0xFF46), 0x00);
+    cgb.ppu.write_byte(0xFF68, 0x02);
+    assert_eq!(cgb.ppu.read_byte(0xFF69), 0xEF); //the second background color is 0x1BEF
+    cgb.ppu.write_byte(0xFF6A, 0x0A);
+    assert_eq!(cgb.ppu.read_byte(0xFF6B), 0x1F); //the second color of the second sprite palette is 0x421F
+  }
+
+  #[test]
+  fn stopped_hblank_dma_reports_the_remaining_length()
+  {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0143] = 0x80; //cgb game
+    rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+    let mut mmu = Mmu::new(load_rom_from_buffer(rom).unwrap(), None, HardwareModel::CGB);
+
+    mmu.write_byte(0xFF51, 0xC0);
+    mmu.write_byte(0xFF52, 0x00);
+    mmu.write_byte(0xFF53, 0x00);
+    mmu.write_byte(0xFF54, 0x00);
+    mmu.write_byte(0xFF55, 0x83); //4 blocks in hblank mode
+    assert_eq!(mmu.read_byte(0xFF55), 0x03);
+
+    for _ in 0 .. 154 * 456 / 4 { //one block per hblank
+      if mmu.read_byte(0xFF55) == 0x02 {
+        break
+      }
+      mmu.do_ticks(4);
+    }
+    assert_eq!(mmu.read_byte(0xFF55), 0x02);
+
+    mmu.write_byte(0xFF55, 0x00); //stop the transfer
+    assert_eq!(mmu.read_byte(0xFF55), 0x82);
+
+    mmu.write_byte(0xFF55, 0x82); //resume with the remaining length
+    assert_eq!(mmu.read_byte(0xFF55), 0x02);
+    for _ in 0 .. 4 * 456 / 4 {
+      mmu.do_ticks(4);
+    }
+    assert_eq!(mmu.read_byte(0xFF55), 0xFF);
+  }
+
+  #[test]
+  fn oam_dma_takes_160_m_cycles_and_blocks_the_bus()
+  {
+    let mut mmu = mmu(None);
+    mmu.write_byte(0xFF40, 0x00); //lcd off so the ppu doesn't block OAM
+    for offset in 0 .. VOAM_SIZE as u16 {
+      mmu.write_byte(0xC000 + offset, offset as u8);
+    }
+    mmu.write_byte(0xFF80, 0x42);
+
+    mmu.write_byte(0xFF46, 0xE0); //E000 is the echo of C000
+    mmu.do_ticks(OAM_DMA_STARTUP * 4);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+    assert_eq!(mmu.read_byte(0xC050), 0x00); //the cpu sees the byte the dma copied
+    assert_eq!(mmu.read_byte(0xFF80), 0x42);
+
+    mmu.do_ticks((VOAM_SIZE - 1) * 4);
+    assert_eq!(mmu.read_byte(0xFE00), 0x00);
+    assert_eq!(mmu.read_byte(0xFE9F), 0x9F);
+    assert_eq!(mmu.read_byte(0xC050), 0x50);
+  }
+
+  #[test]
+  fn ppu_blocks_vram_and_oam_while_drawing()
+  {
+    let mut mmu = mmu(None);
+    mmu.write_byte(0xFF40, 0x00);
+    mmu.write_byte(0x8000, 0x12);
+    mmu.write_byte(0xFE00, 0x34);
+    mmu.write_byte(0xFF40, 0x91);
+    mmu.do_ticks(456); //the first line after turning the lcd on has no mode 2
+
+    mmu.do_ticks(4); //mode 2
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+
+    mmu.do_ticks(80); //mode 3
+    mmu.write_byte(0x8000, 0x56);
+    assert_eq!(mmu.read_byte(0x8000), 0xFF);
+    assert_eq!(mmu.read_byte(0xFE00), 0xFF);
+
+    mmu.do_ticks(200); //mode 0
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), 0x34);
+  }
+}
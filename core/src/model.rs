@@ -0,0 +1,171 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::cpu::registers::{Registers, FlagRegister, CpuFlag};
+use crate::mbc::Mbc;
+use crate::mbc::header::{CartridgeHeader, CgbSupport};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HardwareModel {
+  DMG0, //early japanese DMG with a different boot rom
+  DMG,
+  MGB, //Game Boy Pocket and Light
+  SGB,
+  SGB2,
+  CGB,
+  AGB //Game Boy Advance running a Game Boy game
+}
+
+impl HardwareModel {
+  //the model a game was made for - CGB for color games and DMG for everything else
+  pub fn for_header(header: &CartridgeHeader) -> HardwareModel {
+    match header.cgb_support {
+      CgbSupport::None => HardwareModel::DMG,
+      _ => HardwareModel::CGB
+    }
+  }
+
+  pub fn is_cgb(&self) -> bool {
+    matches!(self, HardwareModel::CGB | HardwareModel::AGB)
+  }
+
+  //the color features are only turned on for games that support them - the others run in DMG compatibility mode
+  pub fn cgb_mode(&self, header: &CartridgeHeader) -> bool {
+    self.is_cgb() && header.cgb_support != CgbSupport::None
+  }
+
+  /*
+    the cpu registers the boot rom of each model leaves behind when it jumps to 0x0100
+    DMG and MGB set H and C if the header checksum is not 0.
+    the CGB boot rom in DMG compatibility mode puts the title checksum of nintendo games in B - the AGB adds one to B in both modes.
+  */
+  pub(crate) fn post_boot_registers(&self, rom: &dyn Mbc) -> Registers {
+    let header = rom.header();
+    let checksum_flags = if header.header_checksum == 0 { 0x80 } else { 0xB0 };
+    let mut registers = Registers::power_up();
+    registers.sp = 0xFFFE;
+    registers.pc = 0x0100;
+
+    let (af, bc, de, hl) = match self {
+      HardwareModel::DMG0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+      HardwareModel::DMG => (0x0100 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+      HardwareModel::MGB => (0xFF00 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+      HardwareModel::SGB => (0x0100, 0x0014, 0x0000, 0xC060),
+      HardwareModel::SGB2 => (0xFF00, 0x0014, 0x0000, 0xC060),
+      HardwareModel::CGB | HardwareModel::AGB => if self.cgb_mode(header) {
+        (0x1180, 0x0000, 0xFF56, 0x000D)
+      } else {
+        let b = if nintendo_licensee(header) { title_checksum(rom) } else { 0x00 };
+        (0x1180, (b as u16) << 8, 0x0008, 0x007C)
+      }
+    };
+
+    registers.set_af(af);
+    registers.set_bc(bc);
+    registers.set_de(de);
+    registers.set_hl(hl);
+
+    if *self == HardwareModel::AGB { //the AGB boot rom ends with an additional INC B
+      registers.b = registers.b.wrapping_add(1);
+      let b = registers.b;
+      registers.set_flag(CpuFlag::Z, b == 0);
+      registers.set_flag(CpuFlag::N, false);
+      registers.set_flag(CpuFlag::H, b & 0x0F == 0);
+    }
+
+    registers
+  }
+
+  //the internal 16 bit divider when the boot rom hands over - DIV is the upper byte
+  pub fn post_boot_divider(&self) -> u16 {
+    match self {
+      HardwareModel::DMG0 => 0x1830,
+      HardwareModel::DMG | HardwareModel::MGB => 0xABCC,
+      HardwareModel::SGB | HardwareModel::SGB2 => 0x0000, //depends on how long the SGB took to receive the header
+      HardwareModel::CGB | HardwareModel::AGB => 0x1EA0
+    }
+  }
+
+  /*
+    the line and the dot the ppu is at when the boot rom hands over - all models are in vblank.
+    DMG0 reads LY=0x91 and STAT=0x81. DMG and MGB are at the end of line 153 where LY already reads 0 and STAT is 0x85.
+    the SGB boot rom sends the header to the SNES and the CGB boot rom fades in its logo - their phase varies, both hand over early in vblank.
+  */
+  pub fn post_boot_ppu_position(&self) -> (u8, usize) {
+    match self {
+      HardwareModel::DMG0 => (145, 24),
+      HardwareModel::DMG | HardwareModel::MGB => (153, 400),
+      HardwareModel::SGB | HardwareModel::SGB2 => (144, 208),
+      HardwareModel::CGB | HardwareModel::AGB => (144, 4)
+    }
+  }
+}
+
+fn title_checksum(rom: &dyn Mbc) -> u8 {
+  (0..16).fold(0u8, |sum, offset| sum.wrapping_add(rom.read_rom(0x0134 + offset)))
+}
+
+fn nintendo_licensee(header: &CartridgeHeader) -> bool {
+  header.old_licensee == 0x01 || (header.old_licensee == 0x33 && header.new_licensee == "01")
+}
+
+impl fmt::Display for HardwareModel {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{:?}", self)
+  }
+}
+
+impl FromStr for HardwareModel {
+  type Err = String;
+
+  fn from_str(value: &str) -> Result<HardwareModel, String> {
+    match value.to_uppercase().as_str() {
+      "DMG0" => Ok(HardwareModel::DMG0),
+      "DMG" => Ok(HardwareModel::DMG),
+      "MGB" => Ok(HardwareModel::MGB),
+      "SGB" => Ok(HardwareModel::SGB),
+      "SGB2" => Ok(HardwareModel::SGB2),
+      "CGB" => Ok(HardwareModel::CGB),
+      "AGB" => Ok(HardwareModel::AGB),
+      _ => Err(format!("unknown hardware model {} - expected DMG0, DMG, MGB, SGB, SGB2, CGB or AGB", value))
+    }
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+  use crate::mbc::load_rom_from_buffer;
+
+  fn rom(cgb_flag: u8) -> Box<dyn Mbc> {
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x0143] = cgb_flag;
+    rom[0x014D] = CartridgeHeader::calculate_header_checksum(&rom);
+    load_rom_from_buffer(rom).unwrap()
+  }
+
+  #[test]
+  fn registers_after_the_boot_rom()
+  {
+    let dmg_rom = rom(0x00);
+    let dmg = HardwareModel::DMG.post_boot_registers(dmg_rom.as_ref());
+    assert_eq!(dmg.get_af(), 0x01B0);
+    assert_eq!(dmg.get_bc(), 0x0013);
+    assert_eq!(dmg.get_de(), 0x00D8);
+    assert_eq!(dmg.get_hl(), 0x014D);
+    assert_eq!(dmg.sp, 0xFFFE);
+    assert_eq!(dmg.pc, 0x0100);
+
+    let cgb_rom = rom(0x80);
+    let cgb = HardwareModel::CGB.post_boot_registers(cgb_rom.as_ref());
+    assert_eq!(cgb.get_af(), 0x1180);
+    assert_eq!(cgb.get_de(), 0xFF56);
+    assert_eq!(cgb.get_hl(), 0x000D);
+
+    let agb = HardwareModel::AGB.post_boot_registers(cgb_rom.as_ref());
+    assert_eq!(agb.get_af(), 0x1100);
+    assert_eq!(agb.get_bc(), 0x0100);
+
+    assert_eq!(HardwareModel::DMG.post_boot_registers(cgb_rom.as_ref()).a, 0x01); //a color game on a DMG
+  }
+}
@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use crate::SCREEN_WIDTH;
+use crate::ppu::Ppu;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+
+const START_DELAY: usize = 6; //dots at the start of mode 3 before the first tile is fetched - the discarded first fetch
+const FETCH_DOTS: usize = 6; //tile number, low byte and high byte take 2 dots each
+const SPRITE_FETCH_DOTS: usize = 11; //a sprite stops the fifo for 6 to 11 dots depending on how far the background fetch is
+
+#[derive(Clone, Copy)]
+struct SpritePixel {
+  color: u8,
+  attributes: u8,
+  oam_index: usize
+}
+
+const TRANSPARENT: SpritePixel = SpritePixel { color: 0, attributes: 0, oam_index: 0 };
+
+/*
+  the pixel fifo draws mode 3 dot by dot like the hardware does
+    the fetcher reads one row of 8 background or window pixels and pushes them when the background fifo is empty
+    every dot one pixel leaves the fifo and is mixed with the sprite fifo - the registers are read the moment they are used
+    SCX & 7 pixels are dropped at the start of the line, the window restarts the fetcher and every sprite stops the fifo while it is fetched
+  so mode 3 takes longer with scrolling, window and sprites and writes during mode 3 affect the rest of the line
+*/
+pub struct PixelFifo {
+  background: VecDeque<(u8, u8)>, //color and CGB attributes
+  sprites: VecDeque<SpritePixel>,
+  pending_sprites: Vec<usize>, //OAM indices of the sprites on this line that were not fetched yet - sorted by X
+  delay: usize,
+  fetcher_dots: usize,
+  fetcher_x: usize, //tile column of the next fetch
+  discard: usize,
+  x: usize, //next pixel on the screen
+  window: bool, //the fetcher reads the window
+  sprite_stall: usize
+}
+
+impl PixelFifo {
+  pub fn new() -> PixelFifo {
+    PixelFifo {
+      background: VecDeque::with_capacity(16),
+      sprites: VecDeque::with_capacity(8),
+      pending_sprites: Vec::with_capacity(10),
+      delay: 0,
+      fetcher_dots: 0,
+      fetcher_x: 0,
+      discard: 0,
+      x: SCREEN_WIDTH,
+      window: false,
+      sprite_stall: 0
+    }
+  }
+}
+
+impl Ppu {
+  pub(super) fn start_fifo_line(&mut self) {
+    let mut sprites = if self.sprite_enable { self.scan_oam() } else { Vec::new() };
+    sprites.sort_by_key(|&sprite| (self.voam[sprite * 4 + 1], sprite)); //fetched in the order the fifo reaches them
+
+    let fifo = &mut self.fifo;
+    fifo.background.clear();
+    fifo.sprites.clear();
+    fifo.pending_sprites = sprites;
+    fifo.delay = START_DELAY;
+    fifo.fetcher_dots = 0;
+    fifo.fetcher_x = 0;
+    fifo.discard = (self.scroll_x & 0x07) as usize;
+    fifo.x = 0;
+    fifo.window = false;
+    fifo.sprite_stall = 0;
+  }
+
+  //one dot of mode 3 - returns true when the line is finished
+  pub(super) fn step_fifo(&mut self) -> bool {
+    if self.fifo.x >= SCREEN_WIDTH {
+      if self.fifo.window {
+        self.window_line += 1;
+      }
+      return true
+    }
+
+    if self.fifo.delay > 0 {
+      self.fifo.delay -= 1;
+      return false
+    }
+
+    if self.fifo.sprite_stall > 0 {
+      self.fifo.sprite_stall -= 1;
+      if self.fifo.sprite_stall == 0 {
+        self.fetch_sprite();
+      }
+      return false
+    }
+
+    self.step_fetcher();
+
+    if self.fifo.background.is_empty() {
+      return false
+    }
+
+    if self.window_x == 0 && self.window_starts() { //WX=0 starts the window before the fine scrolling is done - the window loses SCX & 7 pixels too
+      self.start_window();
+      return false
+    }
+
+    if self.fifo.discard > 0 { //fine scrolling
+      self.fifo.background.pop_front();
+      self.fifo.discard -= 1;
+      return false
+    }
+
+    if self.window_starts() {
+      self.start_window();
+      return false
+    }
+
+    if self.sprite_enable && self.fifo.pending_sprites.first().is_some_and(|&sprite| self.voam[sprite * 4 + 1] as usize <= self.fifo.x + 8) {
+      self.fifo.sprite_stall = SPRITE_FETCH_DOTS - self.fifo.fetcher_dots.min(FETCH_DOTS - 1) - 1; //this dot is the first one of the stall
+      return false
+    }
+
+    let (color, attributes) = self.fifo.background.pop_front().unwrap_or((0, 0));
+    let sprite = self.fifo.sprites.pop_front().unwrap_or(TRANSPARENT);
+    let x = self.fifo.x;
+
+    self.set_bg_pixel(x, color, attributes);
+    if sprite.color > 0 && self.sprite_enable && !self.bg_has_priority(x, sprite.attributes & 0x80 == 0x80) {
+      self.screen_buffer[self.line as usize][x] = self.sprite_color(sprite.attributes, sprite.color);
+    }
+
+    self.fifo.x += 1;
+    false
+  }
+
+  fn window_starts(&self) -> bool {
+    self.window_enable && !self.fifo.window && self.line >= self.window_y && self.window_x <= 166 && self.fifo.x + 7 >= self.window_x as usize
+  }
+
+  //the fetcher restarts with the window - with WX < 7 the window pixels left of the screen are dropped like fine scrolling
+  fn start_window(&mut self) {
+    self.fifo.window = true;
+    self.fifo.background.clear();
+    self.fifo.fetcher_dots = 0;
+    self.fifo.fetcher_x = 0;
+    self.fifo.discard += 7usize.saturating_sub(self.window_x as usize);
+  }
+
+  fn step_fetcher(&mut self) {
+    self.fifo.fetcher_dots += 1;
+    if self.fifo.fetcher_dots < FETCH_DOTS || !self.fifo.background.is_empty() {
+      return
+    }
+
+    let (row, attributes) = if !self.bg_window_priority && !self.cgb_mode { //LCDC bit 0 turns background and window off on the DMG
+      ([0; 8], 0)
+    } else if self.fifo.window {
+      let tile_map_address = if self.window_tilemap_select { 0x1C00 } else { 0x1800 };
+      let entry = tile_map_address + (self.window_line / 8) * 32 + (self.fifo.fetcher_x & 0x1F);
+      self.tile_row(entry, self.window_line % 8)
+    } else {
+      let tile_map_address = if self.bg_tilemap_select { 0x1C00 } else { 0x1800 };
+      let bg_y = (self.line as usize + self.scroll_y as usize) % 256;
+      let bg_x_tile = (self.scroll_x as usize / 8 + self.fifo.fetcher_x) & 0x1F;
+      self.tile_row(tile_map_address + (bg_y / 8) * 32 + bg_x_tile, bg_y % 8)
+    };
+
+    self.fifo.background.extend(row.iter().map(|&color| (color, attributes)));
+    self.fifo.fetcher_dots = 0;
+    self.fifo.fetcher_x += 1;
+  }
+
+  //mixes the sprite into the sprite fifo - pixels of earlier sprites stay unless they are transparent or the CGB gives the lower OAM index priority
+  fn fetch_sprite(&mut self) {
+    let sprite = self.fifo.pending_sprites.remove(0);
+    let color = self.sprite_tile_row(sprite);
+    let attributes = self.voam[sprite * 4 + 3];
+    let sprite_x = self.voam[sprite * 4 + 1] as usize; //screen x + 8
+
+    while self.fifo.sprites.len() < 8 {
+      self.fifo.sprites.push_back(TRANSPARENT);
+    }
+
+    for (pixel, &color) in color.iter().enumerate() {
+      if sprite_x + pixel < self.fifo.x + 8 { //left of the screen
+        continue
+      }
+      let slot = &mut self.fifo.sprites[sprite_x + pixel - self.fifo.x - 8];
+      if color > 0 && (slot.color == 0 || (self.cgb_mode && sprite < slot.oam_index)) {
+        *slot = SpritePixel { color, attributes, oam_index: sprite };
+      }
+    }
+  }
+}
+
+impl SaveState for PixelFifo {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_usize(self.background.len());
+    for &(color, attributes) in self.background.iter() {
+      writer.write_u8(color);
+      writer.write_u8(attributes);
+    }
+    writer.write_usize(self.sprites.len());
+    for pixel in self.sprites.iter() {
+      writer.write_u8(pixel.color);
+      writer.write_u8(pixel.attributes);
+      writer.write_usize(pixel.oam_index);
+    }
+    writer.write_usize(self.pending_sprites.len());
+    for &sprite in self.pending_sprites.iter() {
+      writer.write_usize(sprite);
+    }
+    writer.write_usize(self.delay);
+    writer.write_usize(self.fetcher_dots);
+    writer.write_usize(self.fetcher_x);
+    writer.write_usize(self.discard);
+    writer.write_usize(self.x);
+    writer.write_bool(self.window);
+    writer.write_usize(self.sprite_stall);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.background.clear();
+    for _ in 0 .. reader.read_usize()?.min(16) {
+      let color = reader.read_u8()? & 0x03;
+      let attributes = reader.read_u8()?;
+      self.background.push_back((color, attributes));
+    }
+    self.sprites.clear();
+    for _ in 0 .. reader.read_usize()?.min(8) {
+      let color = reader.read_u8()? & 0x03;
+      let attributes = reader.read_u8()?;
+      let oam_index = reader.read_usize()? % 40;
+      self.sprites.push_back(SpritePixel { color, attributes, oam_index });
+    }
+    self.pending_sprites.clear();
+    for _ in 0 .. reader.read_usize()?.min(10) {
+      self.pending_sprites.push(reader.read_usize()? % 40);
+    }
+    self.delay = reader.read_usize()?;
+    self.fetcher_dots = reader.read_usize()?;
+    self.fetcher_x = reader.read_usize()?;
+    self.discard = reader.read_usize()?;
+    self.x = reader.read_usize()?;
+    self.window = reader.read_bool()?;
+    self.sprite_stall = reader.read_usize()?;
+    Ok(())
+  }
+}
@@ -0,0 +1,1065 @@
+use crate::SCREEN_WIDTH;
+use crate::SCREEN_HEIGHT;
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+use crate::model::HardwareModel;
+
+mod fifo;
+
+use self::fifo::PixelFifo;
+
+pub const VRAM_SIZE: usize = 0x2000; //8kB vram - the CGB has two banks
+pub const VOAM_SIZE: usize = 0xA0;
+const PALETTE_RAM_SIZE: usize = 0x40; //CGB 8 palettes with 4 colors of 2 bytes
+const MAX_SPRITES_PER_LINE: usize = 10;
+const FRAME_TICKS: usize = 70224; //154 lines * 456 ticks
+
+//the DMG shades as 15 bit colors (bits 0-4 red, 5-9 green, 10-14 blue) like the CGB palettes use them
+const DMG_COLORS: [u16; 4] = [rgb(0x1C, 0x1F, 0x1A), rgb(0x11, 0x18, 0x0E), rgb(0x06, 0x0D, 0x0A), rgb(0x01, 0x03, 0x04)];
+
+const fn rgb(red: u16, green: u16, blue: u16) -> u16 {
+  red | green << 5 | blue << 10
+}
+
+pub struct Ppu {
+  pub irq_vblank: bool,
+  pub irq_stat: bool,
+  pub frame_ready: bool, //set when a new frame was copied to the frame buffer
+  pub hblank_started: bool, //set when mode 0 is entered - the CGB HDMA copies one block per hblank
+
+  screen_buffer: [[u16; SCREEN_WIDTH]; SCREEN_HEIGHT],
+  color_buffer: [[u8; SCREEN_WIDTH]; SCREEN_HEIGHT],
+  bg_priority: [bool; SCREEN_WIDTH], //CGB background attribute bit 7 of the current line
+  frame_buffer: Vec<u16>, //the last finished frame - the screen buffer is drawn into while the frame is rendered
+
+  cgb_mode: bool,
+  dmg_compatibility: bool, //a DMG game on a CGB - the shades of the DMG palettes pick colors of the first CGB palettes
+  stat_write_bug: bool, //writing STAT on a DMG briefly enables all STAT interrupt sources
+  clock: usize,
+  vram: [u8; VRAM_SIZE * 2],
+  vram_bank: usize, //VBK FF4F - CGB only
+  voam: [u8; VOAM_SIZE],
+  lcd_enabled: bool, //FF40
+  window_tilemap_select: bool, //FF40 - false = 9800-9BFF / true = 9C00-9FFF
+  window_enable: bool, //FF40
+  bg_window_tile_addressing: bool, //FF40 - false = 8800-97FF / true = 8000-8FFF
+  bg_tilemap_select: bool, //FF40 false = 9800-9BFF / true = 9C00-9FFF
+  sprite_size: usize, //FF40 false = 8x8 / true = 8x16
+  sprite_enable: bool, //FF40
+  bg_window_priority: bool, //FF40
+  mode: u8, //Mode 0,1,2,3 FF41
+  irq_m0_enable: bool, //sets what triggers the stat interrupt FF41
+  irq_m1_enable: bool,
+  irq_m2_enable: bool,
+  irq_lyc_enable: bool,
+  stat_line: bool, //all enabled STAT sources are combined to one line - the interrupt is requested when it goes high
+  coincidence: bool, //LY=LYC flag of STAT
+  scroll_y: u8, //SCY FF42
+  scroll_x: u8, //SCX FF43
+  line: u8, //LY FF44 current line drawn by the display controller
+  line_compare: u8, //LYC FF45 compare value for LYC
+  bg_palette: u8, //BGP FF47
+  obj_palette_1: u8, //OBP0 FF48
+  obj_palette_2: u8, //OBP1 FF49
+  window_y: u8, //WY FF4A 0
+  window_x: u8, //WX FF4B 7
+  window_line: usize, //internal line counter of the window
+  bg_color_index: u8, //BCPS FF68 - bit 7 increments the index after every write to BCPD
+  bg_color_ram: [u8; PALETTE_RAM_SIZE], //BCPD FF69
+  obj_color_index: u8, //OCPS FF6A
+  obj_color_ram: [u8; PALETTE_RAM_SIZE], //OCPD FF6B
+
+  skip_frame: bool, //the first frame after the lcd is turned on is not shown
+
+  pixel_fifo: bool, //draw mode 3 dot by dot instead of the whole line at once
+  fifo: PixelFifo,
+}
+
+impl Ppu {
+  pub fn new(cgb_mode: bool, model: HardwareModel) -> Ppu {
+    Ppu {
+      screen_buffer: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+      color_buffer: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
+      bg_priority: [false; SCREEN_WIDTH],
+      frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+      irq_vblank: false,
+      irq_stat: false,
+      frame_ready: false,
+      hblank_started: false,
+      cgb_mode,
+      dmg_compatibility: model.is_cgb() && !cgb_mode,
+      stat_write_bug: !model.is_cgb(),
+      clock: 0, // for the first line
+      vram: [0; VRAM_SIZE * 2],
+      vram_bank: 0,
+      voam: [0; VOAM_SIZE],
+      lcd_enabled: false,
+      window_tilemap_select: false,
+      window_enable: false,
+      bg_window_tile_addressing: false,
+      bg_tilemap_select: false,
+      sprite_size: 8,
+      sprite_enable: false,
+      bg_window_priority: false,
+      mode: 0x00,
+      irq_m0_enable: false,
+      irq_m1_enable: false,
+      irq_m2_enable: false,
+      irq_lyc_enable: false,
+      stat_line: false,
+      coincidence: false,
+      scroll_y: 0x00,
+      scroll_x: 0x00,
+      line: 0x00,
+      line_compare: 0x00,
+      bg_palette: 0xFC,
+      obj_palette_1: 0xFF,
+      obj_palette_2: 0xFF,
+      window_y: 0x00,
+      window_x: 0x00,
+      window_line: 0,
+      bg_color_index: 0,
+      bg_color_ram: [0xFF; PALETTE_RAM_SIZE], //the boot rom leaves all background colors white
+      obj_color_index: 0,
+      obj_color_ram: [0xFF; PALETTE_RAM_SIZE],
+      skip_frame: false,
+      pixel_fifo: false,
+      fifo: PixelFifo::new(),
+    }
+  }
+
+  pub fn read_byte(&self, address: u16) -> u8 {
+    match address {
+      0x8000..=0x9FFF => { let offset = address as usize - 0x8000; self.vram[self.vram_bank * VRAM_SIZE + offset] },
+      0xFE00..=0xFE9F => { let offset = address as usize - 0xFE00; self.voam[offset] },
+      0xFF40 => { // LCD Control
+        (if self.lcd_enabled { 0x80 } else { 0x00 }) |
+        (if self.window_tilemap_select { 0x40 } else { 0x00 }) |
+        (if self.window_enable { 0x20 } else { 0x00 }) |
+        (if self.bg_window_tile_addressing { 0x10 } else { 0x00 }) |
+        (if self.bg_tilemap_select { 0x08 } else { 0x00 }) |
+        (if self.sprite_size == 16 { 0x04 } else { 0x00 }) |
+        (if self.sprite_enable { 0x02 } else { 0x00 }) |
+        (if self.bg_window_priority { 0x01 } else { 0x00 })
+      },
+      0xFF41 => { // LCD Status - bit 7 is unused and reads 1
+        0x80 |
+        (if self.irq_lyc_enable { 0x40 } else { 0x00 }) |
+        (if self.irq_m2_enable { 0x20 } else { 0x00 }) |
+        (if self.irq_m1_enable { 0x10 } else { 0x00 }) |
+        (if self.irq_m0_enable { 0x08 } else { 0x00 }) |
+        (if self.coincidence { 0x04 } else { 0x00 }) |
+        self.mode
+      },
+      0xFF42 => self.scroll_y,
+      0xFF43 => self.scroll_x,
+      0xFF44 => self.ly(),
+      0xFF45 => self.line_compare,
+      0xFF47 => self.bg_palette,
+      0xFF48 => self.obj_palette_1,
+      0xFF49 => self.obj_palette_2,
+      0xFF4A => self.window_y,
+      0xFF4B => self.window_x,
+      0xFF4F => 0xFE | self.vram_bank as u8,
+      0xFF68 => self.bg_color_index | 0x40,
+      0xFF69 => self.bg_color_ram[(self.bg_color_index & 0x3F) as usize],
+      0xFF6A => self.obj_color_index | 0x40,
+      0xFF6B => self.obj_color_ram[(self.obj_color_index & 0x3F) as usize],
+      _ => panic!("Invalid read at GPU memory adress: {:#06X}", address)
+    }
+  }
+
+  pub fn write_byte(&mut self, address: u16, value: u8) {
+    match address {
+      0x8000..=0x9FFF => { let offset = address as usize - 0x8000; self.vram[self.vram_bank * VRAM_SIZE + offset] = value; },
+      0xFE00..=0xFE9F => { let offset = address as usize - 0xFE00; self.voam[offset] = value; },
+      0xFF40 => {
+        let lcd_enabled = value & 0x80 == 0x80;
+        if lcd_enabled != self.lcd_enabled {
+          self.switch_lcd(lcd_enabled);
+        }
+        self.window_tilemap_select = value & 0x40 == 0x40;
+        self.window_enable = value & 0x20 == 0x20;
+        self.bg_window_tile_addressing = value & 0x10 == 0x10;
+        self.bg_tilemap_select = value & 0x08 == 0x08;
+        self.sprite_size = if value & 0x04 == 0x04 { 16 } else { 8 };
+        self.sprite_enable = value & 0x02 == 0x02;
+        self.bg_window_priority = value & 0x01 == 0x01;
+      },
+      0xFF41 => {
+        //the DMG acts as if all sources were enabled for one cycle - games like Road Rash depend on the interrupt that can cause
+        if self.stat_write_bug && self.lcd_enabled && !self.stat_line && (self.mode == 0 || self.mode == 1 || self.coincidence) {
+          self.irq_stat = true;
+        }
+        self.irq_lyc_enable = value & 0x40 == 0x40;
+        self.irq_m2_enable = value & 0x20 == 0x20;
+        self.irq_m1_enable = value & 0x10 == 0x10;
+        self.irq_m0_enable = value & 0x08 == 0x08;
+        self.update_stat();
+      }
+      0xFF42 => self.scroll_y = value,
+      0xFF43 => self.scroll_x = value,
+      0xFF45 => { self.line_compare = value; self.update_stat() },
+      0xFF44 => self.line = 0,
+      0xFF47 => self.bg_palette = value,
+      0xFF48 => self.obj_palette_1 = value,
+      0xFF49 => self.obj_palette_2 = value,
+      0xFF4A => self.window_y = value,
+      0xFF4B => self.window_x = value,
+      0xFF4F => self.vram_bank = (value & 0x01) as usize,
+      0xFF68 => self.bg_color_index = value & 0xBF,
+      0xFF69 => Ppu::write_color_ram(&mut self.bg_color_ram, &mut self.bg_color_index, value),
+      0xFF6A => self.obj_color_index = value & 0xBF,
+      0xFF6B => Ppu::write_color_ram(&mut self.obj_color_ram, &mut self.obj_color_index, value),
+      _ => panic!("Invalid write at GPU memory adress: {:#06X} - {:#06X}", address, value)
+    }
+  }
+
+  fn write_color_ram(color_ram: &mut [u8; PALETTE_RAM_SIZE], index: &mut u8, value: u8) {
+    color_ram[(*index & 0x3F) as usize] = value;
+    if *index & 0x80 == 0x80 { //auto increment
+      *index = 0x80 | ((*index + 1) & 0x3F);
+    }
+  }
+
+  //colors are stored little endian as 0bbbbbgggggrrrrr
+  fn palette_color(color_ram: &[u8; PALETTE_RAM_SIZE], palette: u8, color: u8) -> u16 {
+    let index = (palette as usize & 0x07) * 8 + color as usize * 2;
+    (color_ram[index] as u16 | (color_ram[index + 1] as u16) << 8) & 0x7FFF
+  }
+
+  /*
+    the ppu state the boot rom leaves behind - the lcd is on and the ppu is in vblank.
+    for DMG games the CGB boot rom loads the compatibility palettes. nintendo games get their own colors picked by the title checksum,
+    the others get the default - dark green background and red sprites. we give the default to all of them.
+  */
+  pub fn post_boot(&mut self, model: HardwareModel) {
+    let (line, clock) = model.post_boot_ppu_position();
+    self.line = line;
+    self.clock = clock;
+    self.mode = 1;
+    self.skip_frame = false; //the lcd has been on for a while
+    self.update_stat();
+
+    if self.dmg_compatibility {
+      Ppu::load_palette(&mut self.bg_color_ram, 0, &[0x7FFF, 0x1BEF, 0x6180, 0x0000]);
+      Ppu::load_palette(&mut self.obj_color_ram, 0, &[0x7FFF, 0x421F, 0x1CF2, 0x0000]);
+      Ppu::load_palette(&mut self.obj_color_ram, 1, &[0x7FFF, 0x421F, 0x1CF2, 0x0000]);
+    }
+  }
+
+  fn load_palette(color_ram: &mut [u8; PALETTE_RAM_SIZE], palette: usize, colors: &[u16; 4]) {
+    for (index, color) in colors.iter().enumerate() {
+      color_ram[palette * 8 + index * 2] = *color as u8;
+      color_ram[palette * 8 + index * 2 + 1] = (*color >> 8) as u8;
+    }
+  }
+
+  //the DMG palettes map a color to one of four shades - in DMG compatibility mode the shade picks a color of a CGB palette
+  fn dmg_color(&self, color_ram: &[u8; PALETTE_RAM_SIZE], cgb_palette: u8, palette: u8, color: u8) -> u16 {
+    let shade = (palette >> (color * 2)) & 0x03;
+    if self.dmg_compatibility {
+      Ppu::palette_color(color_ram, cgb_palette, shade)
+    } else {
+      DMG_COLORS[shade as usize]
+    }
+  }
+
+  //the pixel fifo is slower but shows register changes in the middle of a line and varies the length of mode 3
+  pub fn set_pixel_fifo(&mut self, pixel_fifo: bool) {
+    self.pixel_fifo = pixel_fifo;
+  }
+
+  /*
+    turning the lcd off resets LY and the mode to 0 - the screen stays blank until the lcd is on again.
+    after turning it on the ppu starts line 0 in mode 0 instead of the OAM search and the first frame is not shown.
+  */
+  fn switch_lcd(&mut self, enabled: bool) {
+    self.lcd_enabled = enabled;
+    self.line = 0;
+    self.clock = 0;
+    self.mode = 0;
+    self.window_line = 0;
+    self.stat_line = false;
+    self.skip_frame = enabled;
+    if enabled {
+      self.update_stat(); //LY=LYC is compared right away
+    }
+  }
+
+  fn blank_frame(&mut self) {
+    let white = if self.cgb_mode || self.dmg_compatibility { 0x7FFF } else { DMG_COLORS[0] };
+    self.frame_buffer.fill(white);
+    self.frame_ready = true;
+  }
+
+  pub fn get_frame_buffer(&self) -> &[u16] {
+    &self.frame_buffer
+  }
+
+  fn finish_frame(&mut self) {
+    for (row, frame_row) in self.screen_buffer.iter().zip(self.frame_buffer.chunks_mut(SCREEN_WIDTH)) {
+      frame_row.copy_from_slice(row);
+    }
+    self.frame_ready = true;
+  }
+
+  /* timing
+    OAM search - 80 - determine which sprites are visible
+    pixel transfer - 172 - draw the stuff
+    Horizontal blank 204
+    Single line 456
+    Vertical blank 4560
+    Entire frame 70224 */
+  pub fn do_ticks(&mut self, ticks: usize) {
+    if !self.lcd_enabled { //a blank frame at the normal rate keeps the display and the audio going
+      self.clock += ticks;
+      if self.clock >= FRAME_TICKS {
+        self.clock -= FRAME_TICKS;
+        self.blank_frame();
+      }
+      return
+    }
+
+    let mut ticks = ticks;
+    while ticks > 0 { //the STAT line is checked every M-cycle - the pixel fifo runs every dot
+      let step = if self.pixel_fifo { 1 } else { ticks.min(4) };
+      ticks -= step;
+      self.clock += step;
+
+      if self.clock >= 456 { //advance one line
+        self.clock -= 456;
+        self.line = (self.line + 1) % 154; //154 = 144 physical lines + 10 imaginary vblank lines
+
+        if self.line == 144 {
+          self.set_mode(1);
+        }
+      }
+
+      let oam_search = !(self.skip_frame && self.line == 0); //the first line after turning the lcd on has no mode 2
+
+      if self.line < 144 && self.pixel_fifo {
+        if self.clock <= 80 {
+          if self.mode != 2 && oam_search { self.set_mode(2); }
+        } else if self.clock == 81 {
+          self.set_mode(3);
+        } else if self.mode == 3 && self.step_fifo() {
+          self.set_mode(0);
+        }
+      } else if self.line < 144 {
+        if self.clock <= 80 {
+          if self.mode != 2 && oam_search { self.set_mode(2); }
+        } else if self.clock <= 80 + 172 {
+          if self.mode != 3 { self.set_mode(3); }
+        } else if self.mode != 0 {
+          self.set_mode(0);
+        }
+      }
+
+      self.update_stat();
+    }
+  }
+
+  //LY reads 0 for most of line 153 - the frame really ends there
+  fn ly(&self) -> u8 {
+    if self.line == 153 && self.clock >= 4 { 0 } else { self.line }
+  }
+
+  /*
+    the line LYC is compared with - after LY changed the comparison is skipped for one M-cycle.
+    on line 153 LY=153 is only compared for one M-cycle before LY=0 is compared for the rest of the line and all of line 0.
+  */
+  fn compare_line(&self) -> Option<u8> {
+    match (self.line, self.clock) {
+      (0, _) => Some(0),
+      (153, 4 ..= 7) => Some(153),
+      (153, 12 ..= 455) => Some(0),
+      (153, _) => None,
+      (_, 0 ..= 3) => None,
+      (line, _) => Some(line)
+    }
+  }
+
+  //the interrupt is only requested when the combined line goes from low to high - a source can't trigger while another one keeps the line high
+  fn update_stat(&mut self) {
+    if !self.lcd_enabled {
+      return
+    }
+
+    if let Some(line) = self.compare_line() {
+      self.coincidence = line == self.line_compare;
+    } else {
+      self.coincidence = false;
+    }
+
+    let stat_line =
+      (self.irq_lyc_enable && self.coincidence) ||
+      (self.irq_m0_enable && self.mode == 0) ||
+      (self.irq_m1_enable && self.mode == 1) ||
+      (self.irq_m2_enable && (self.mode == 2 || (self.line == 144 && self.clock < 4))); //the mode 2 source also fires when vblank starts
+
+    if stat_line && !self.stat_line {
+      self.irq_stat = true;
+    }
+    self.stat_line = stat_line;
+  }
+
+  /*
+ Mode 0: The LCD controller is in the H-Blank period and
+         the CPU can access both the display RAM (8000h-9FFFh)
+         and OAM (FE00h-FE9Fh)
+
+ Mode 1: The LCD controller is in the V-Blank period (or the
+         display is disabled) and the CPU can access both the
+         display RAM (8000h-9FFFh) and OAM (FE00h-FE9Fh)
+
+ Mode 2: The LCD controller is reading from OAM memory.
+         The CPU <cannot> access OAM memory (FE00h-FE9Fh)
+         during this period.
+
+ Mode 3: The LCD controller is reading from both OAM and VRAM,
+         The CPU <cannot> access OAM and VRAM during this period.
+         CGB Mode: Cannot access Palette Data (FF69,FF6B) either.
+*/
+  //the cpu can't access what the ppu is reading - reads return 0xFF and writes are ignored
+  pub fn blocks_cpu(&self, address: u16) -> bool {
+    if !self.lcd_enabled {
+      return false
+    }
+
+    match address {
+      0x8000..=0x9FFF => self.mode == 3,
+      0xFE00..=0xFE9F => self.mode == 2 || self.mode == 3,
+      0xFF69 | 0xFF6B => self.cgb_mode && self.mode == 3,
+      _ => false
+    }
+  }
+
+  fn set_mode(&mut self, mode: u8) {
+    //println!("set mode: {}", mode);
+    self.mode = mode;
+
+    match mode {
+      1 => { //we finished the screen, tell the window to refresh
+        self.irq_vblank = true;
+        self.window_line = 0;
+        if self.skip_frame {
+          self.skip_frame = false;
+          self.blank_frame();
+        } else {
+          self.finish_frame();
+        }
+      },
+      2 => (), //determine visible sprites
+      3 => if self.pixel_fifo { self.start_fifo_line() } else { self.render_line() }, //draw the current line
+      _ => self.hblank_started = true //in Mode 0 and 1 the PPU idles and the CPU can access the memmory
+    }
+  }
+
+  fn render_line(&mut self) {
+    if self.bg_window_priority || self.cgb_mode { //on the DMG bit 0 of LCDC turns background and window off - on the CGB the sprites are drawn above them instead
+      self.render_background();
+
+      if self.window_enable {
+        self.render_window();
+      }
+    } else {
+      let y = self.line as usize;
+      for x in 0 .. SCREEN_WIDTH {
+        self.screen_buffer[y][x] = self.dmg_color(&self.bg_color_ram, 0, self.bg_palette, 0);
+        self.color_buffer[y][x] = 0;
+        self.bg_priority[x] = false;
+      }
+    }
+
+    if self.sprite_enable {
+      self.render_sprites();
+    }
+  }
+
+  fn render_background(&mut self) {
+    let tile_map_address = if self.bg_tilemap_select { 0x1C00 } else { 0x1800 };
+
+    let mut current_tile = 0; //address of the currently selected tile
+    let mut color:[u8;8] = [0;8];
+    let mut attributes = 0;
+
+    let y = self.line as usize; //the line index
+    let bg_y = (y + self.scroll_y as usize) % 256;
+    let bg_y_tile = bg_y / 8;  // there are 32x32 tiles that are 8x8 - find the tile index by divide through 8
+    let bg_y_offset = bg_y % 8; //the offset of the line inside the sprite;
+
+    for x in 0 .. SCREEN_WIDTH { //draw one pixel after the other
+      let bg_x = (x + self.scroll_x as usize) % 256;
+      let bg_x_tile = bg_x / 8;
+      let bg_x_offset = bg_x % 8;
+
+      let tile_selected_tile = tile_map_address + (bg_y_tile * 32) + bg_x_tile;
+      if tile_selected_tile != current_tile {
+        let (row, row_attributes) = self.tile_row(tile_selected_tile, bg_y_offset);
+        color = row;
+        attributes = row_attributes;
+        current_tile = tile_selected_tile;
+      }
+
+      self.set_bg_pixel(x, color[bg_x_offset], attributes);
+    }
+  }
+
+  /*
+    The window is visible when WY <= LY and WX is in 0-166. It starts at screen x = WX - 7
+    and covers the background up to the right edge. The window keeps its own line counter
+    that only advances on lines where the window was drawn, so hiding it for a few lines
+    continues it where it stopped instead of skipping rows.
+  */
+  fn render_window(&mut self) {
+    if self.line < self.window_y || self.window_x > 166 {
+      return
+    }
+
+    let tile_map_address = if self.window_tilemap_select { 0x1C00 } else { 0x1800 };
+
+    let window_y_tile = self.window_line / 8;
+    let window_y_offset = self.window_line % 8;
+
+    let mut current_tile = 0;
+    let mut color:[u8;8] = [0;8];
+    let mut attributes = 0;
+
+    let start_x = (self.window_x as usize).saturating_sub(7); //WX=7 is the left edge of the screen
+    for x in start_x .. SCREEN_WIDTH {
+      let window_x = x + 7 - self.window_x as usize;
+      let window_x_tile = window_x / 8;
+      let window_x_offset = window_x % 8;
+
+      let tile_selected_tile = tile_map_address + (window_y_tile * 32) + window_x_tile;
+      if tile_selected_tile != current_tile {
+        let (row, row_attributes) = self.tile_row(tile_selected_tile, window_y_offset);
+        color = row;
+        attributes = row_attributes;
+        current_tile = tile_selected_tile;
+      }
+
+      self.set_bg_pixel(x, color[window_x_offset], attributes); //the sprite priority treats the window like the background
+    }
+
+    self.window_line += 1;
+  }
+
+  /*
+    CGB background attributes are stored in vram bank 1 at the address of the tile map entry
+      Bit7   BG-to-OAM Priority (0=Use OAM priority bit, 1=BG Priority)
+      Bit6   Vertical Flip
+      Bit5   Horizontal Flip
+      Bit3   Tile VRAM Bank number
+      Bit2-0 Background Palette number (BGP0-7)
+  */
+  //the color values of one row of the tile referenced at the given tile map address and its attributes - shared by background and window
+  fn tile_row(&self, tile_map_entry: usize, row: usize) -> ([u8;8], u8) {
+    let tile_offset = self.vram[tile_map_entry];
+    let attributes = if self.cgb_mode { self.vram[VRAM_SIZE + tile_map_entry] } else { 0 };
+
+    let tile_address = if self.bg_window_tile_addressing { tile_offset as usize * 16 } else { 0x1000u16.wrapping_add((tile_offset as i8 as i16 * 16) as u16) as usize }; //false = 8800-97FF / true = 8000-8FFF
+    let bank_offset = if attributes & 0x08 == 0x08 { VRAM_SIZE } else { 0 };
+    let row = if attributes & 0x40 == 0x40 { 7 - row } else { row };
+    let byte_address = bank_offset + tile_address + (row * 2);
+
+    let mut color = Ppu::sprite_row(self.vram[byte_address], self.vram[byte_address + 1]);
+    if attributes & 0x20 == 0x20 {
+      color.reverse();
+    }
+    (color, attributes)
+  }
+
+  fn set_bg_pixel(&mut self, x: usize, color: u8, attributes: u8) {
+    let y = self.line as usize;
+    self.screen_buffer[y][x] = if self.cgb_mode {
+      Ppu::palette_color(&self.bg_color_ram, attributes, color)
+    } else {
+      self.dmg_color(&self.bg_color_ram, 0, self.bg_palette, color)
+    };
+    self.color_buffer[y][x] = color;
+    self.bg_priority[x] = attributes & 0x80 == 0x80;
+  }
+
+  /*
+    OAM scan - the first 10 sprites in OAM that cover the line are drawn. sprites outside of the screen horizontally still count.
+    on the DMG the sprite with the lower X wins and the OAM index decides between equal X - on the CGB only the OAM index counts.
+  */
+  fn scan_oam(&self) -> Vec<usize> {
+    let line = self.line as usize + 16;
+    let mut sprites: Vec<usize> = (0..40)
+      .filter(|sprite| { let y = self.voam[sprite * 4] as usize; line >= y && line < y + self.sprite_size })
+      .take(MAX_SPRITES_PER_LINE)
+      .collect();
+
+    if !self.cgb_mode {
+      sprites.sort_by_key(|&sprite| (self.voam[sprite * 4 + 1], sprite)); //stable for equal X
+    }
+    sprites
+  }
+
+  fn render_sprites(&mut self) {
+    let mut covered = [false; SCREEN_WIDTH]; //a pixel belongs to the sprite with the highest priority even if the background hides it
+
+    for sprite in self.scan_oam() {
+      let x = self.voam[sprite * 4 + 1] as usize;
+      let sprite_attributes = self.voam[sprite * 4 + 3];
+      let behind_bg = sprite_attributes & 0x80 == 0x80;
+
+      let color = self.sprite_tile_row(sprite);
+      for (x_offset, &pixel) in color.iter().enumerate() {
+        if pixel == 0 || x + x_offset < 8 {
+          continue
+        }
+
+        let screen_x = x + x_offset - 8;
+        if screen_x < SCREEN_WIDTH && !covered[screen_x] {
+          covered[screen_x] = true;
+          if !self.bg_has_priority(screen_x, behind_bg) {
+            self.screen_buffer[self.line as usize][screen_x] = self.sprite_color(sprite_attributes, pixel);
+          }
+        }
+      }
+    }
+  }
+
+  /*  Bit7   OBJ-to-BG Priority (0=OBJ Above BG, 1=OBJ Behind BG color 1-3)
+    (Used for both BG and Window. BG color 0 is always behind OBJ)
+    Bit6   Y flip          (0=Normal, 1=Vertically mirrored)
+    Bit5   X flip          (0=Normal, 1=Horizontally mirrored)
+    Bit4   Palette number  **Non CGB Mode Only** (0=OBP0, 1=OBP1)
+    Bit3   Tile VRAM-Bank  **CGB Mode Only**     (0=Bank 0, 1=Bank 1)
+    Bit2-0 Palette number  **CGB Mode Only**     (OBP0-7) */
+  //the color values of the sprite on the current line from left to right - shared by the line renderer and the pixel fifo
+  fn sprite_tile_row(&self, sprite: usize) -> [u8;8] {
+    let sprite_address = sprite * 4;
+    let y = self.voam[sprite_address] as usize;
+    let sprite_id = if self.sprite_size == 16 { self.voam[sprite_address + 2] & 0xFE } else { self.voam[sprite_address + 2] }; //8x16 sprites ignore bit 0 of the tile
+    let sprite_attributes = self.voam[sprite_address + 3];
+    let line = self.line as usize + 16;
+
+    let bank_offset = if self.cgb_mode && sprite_attributes & 0x08 == 0x08 { VRAM_SIZE } else { 0 };
+    let sprite_start_address = bank_offset + sprite_id as usize * 16;
+    let sprite_line = if sprite_attributes & 0x40 == 0x40 { y + self.sprite_size - 1 - line } else { line - y };
+
+    let first = self.vram[sprite_start_address + sprite_line * 2];
+    let second = self.vram[sprite_start_address + sprite_line * 2 + 1];
+
+    let mut color = Ppu::sprite_row(first, second);
+    if sprite_attributes & 0x20 == 0x20 {
+      color.reverse();
+    }
+    color
+  }
+
+  fn sprite_color(&self, sprite_attributes: u8, color: u8) -> u16 {
+    if self.cgb_mode {
+      Ppu::palette_color(&self.obj_color_ram, sprite_attributes, color)
+    } else {
+      let (cgb_palette, palette) = if sprite_attributes & 0x10 == 0x10 { (1, self.obj_palette_2) } else { (0, self.obj_palette_1) };
+      self.dmg_color(&self.obj_color_ram, cgb_palette, palette, color)
+    }
+  }
+
+  //background color 0 is always behind the sprites - on the CGB LCDC bit 0 off puts all sprites in front
+  fn bg_has_priority(&self, x: usize, sprite_behind_bg: bool) -> bool {
+    let bg_color = self.color_buffer[self.line as usize][x];
+    if self.cgb_mode {
+      self.bg_window_priority && bg_color > 0 && (sprite_behind_bg || self.bg_priority[x])
+    } else {
+      bg_color > 0 && sprite_behind_bg
+    }
+  }
+
+  fn sprite_row(first: u8, second: u8) -> [u8;8] {
+    //println!("{:#06X} {:#06X}", first, second);
+    let mut result = [0u8; 8];
+    for i in 0 .. 8 {
+      let bit_index = 7 - i; // bit 7 left most bit 0 right most
+      result[i] = ((first >> bit_index) & 0x01 ) | (((second >> bit_index) & 0x01) << 1);
+    }
+    result
+  }
+}
+
+impl SaveState for Ppu {
+  fn save_state(&self, writer: &mut StateWriter) {
+    for pixel in self.screen_buffer.iter().flat_map(|row| row.iter()) {
+      writer.write_u16(*pixel);
+    }
+    for row in self.color_buffer.iter() {
+      writer.write_bytes(row);
+    }
+    writer.write_usize(self.clock);
+    writer.write_bytes(&self.vram);
+    writer.write_bytes(&self.voam);
+    writer.write_u8(self.read_byte(0xFF40));
+    writer.write_u8(self.read_byte(0xFF41));
+    writer.write_u8(self.scroll_y);
+    writer.write_u8(self.scroll_x);
+    writer.write_u8(self.line);
+    writer.write_u8(self.line_compare);
+    writer.write_u8(self.bg_palette);
+    writer.write_u8(self.obj_palette_1);
+    writer.write_u8(self.obj_palette_2);
+    writer.write_u8(self.window_y);
+    writer.write_u8(self.window_x);
+    writer.write_usize(self.window_line);
+    writer.write_bool(self.irq_vblank);
+    writer.write_bool(self.irq_stat);
+    writer.write_bool(self.stat_line);
+    writer.write_bool(self.coincidence);
+    writer.write_bool(self.skip_frame);
+    writer.write_usize(self.vram_bank);
+    writer.write_u8(self.bg_color_index);
+    writer.write_bytes(&self.bg_color_ram);
+    writer.write_u8(self.obj_color_index);
+    writer.write_bytes(&self.obj_color_ram);
+    self.fifo.save_state(writer);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    for pixel in self.screen_buffer.iter_mut().flat_map(|row| row.iter_mut()) {
+      *pixel = reader.read_u16()?;
+    }
+    for row in self.color_buffer.iter_mut() {
+      reader.read_bytes(row)?;
+    }
+    self.clock = reader.read_usize()?;
+    reader.read_bytes(&mut self.vram)?;
+    reader.read_bytes(&mut self.voam)?;
+    let lcdc = reader.read_u8()?;
+    self.lcd_enabled = lcdc & 0x80 == 0x80; //restores the state without turning the lcd on or off
+    self.write_byte(0xFF40, lcdc);
+    let stat = reader.read_u8()?;
+    self.write_byte(0xFF41, stat);
+    self.mode = stat & 0x03;
+    self.scroll_y = reader.read_u8()?;
+    self.scroll_x = reader.read_u8()?;
+    self.line = reader.read_u8()?;
+    self.line_compare = reader.read_u8()?;
+    self.bg_palette = reader.read_u8()?;
+    self.obj_palette_1 = reader.read_u8()?;
+    self.obj_palette_2 = reader.read_u8()?;
+    self.window_y = reader.read_u8()?;
+    self.window_x = reader.read_u8()?;
+    self.window_line = reader.read_usize()?.min(SCREEN_HEIGHT - 1); //the window can't have drawn more lines than the screen has
+    self.irq_vblank = reader.read_bool()?;
+    self.irq_stat = reader.read_bool()?;
+    self.stat_line = reader.read_bool()?;
+    self.coincidence = reader.read_bool()?;
+    self.skip_frame = reader.read_bool()?;
+    self.vram_bank = reader.read_usize()? & 0x01;
+    self.bg_color_index = reader.read_u8()?;
+    reader.read_bytes(&mut self.bg_color_ram)?;
+    self.obj_color_index = reader.read_u8()?;
+    reader.read_bytes(&mut self.obj_color_ram)?;
+    self.fifo.load_state(reader)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn sprite_rows_to_color_values()
+  {
+    let first =  0b01010101;
+    let second = 0b11000011;
+
+    let result = Ppu::sprite_row(first, second);
+
+    assert_eq!(result[0],  2);
+    assert_eq!(result[1],  3);
+    assert_eq!(result[2],  0);
+    assert_eq!(result[3],  1);
+    assert_eq!(result[4],  0);
+    assert_eq!(result[5],  1);
+    assert_eq!(result[6],  2);
+    assert_eq!(result[7],  3);
+  }
+
+  #[test]
+  fn window_starts_at_wx_minus_7_and_keeps_its_own_line_counter()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.write_byte(0xFF40, 0xF1); //lcd, window at 9C00, window, tiles at 8000, bg
+    ppu.write_byte(0xFF47, 0xE4); //identity palette
+    ppu.write_byte(0xFF4A, 10); //WY
+    ppu.write_byte(0xFF4B, 17); //WX - the window starts at x = 10
+
+    for row in 0..8 { //tile 1 is solid color 3 in its first row and color 1 in the others
+      ppu.write_byte(0x8010 + row * 2, 0xFF);
+      ppu.write_byte(0x8011 + row * 2, if row == 0 { 0xFF } else { 0x00 });
+    }
+    for tile in 0..32 {
+      ppu.write_byte(0x9C00 + tile, 1);
+    }
+
+    ppu.line = 9;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[9][10], DMG_COLORS[0]);
+
+    ppu.line = 10;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[10][9], DMG_COLORS[0]);
+    assert_eq!(ppu.screen_buffer[10][10], DMG_COLORS[3]);
+
+    ppu.write_byte(0xFF40, 0xD1); //hide the window for a line
+    ppu.line = 11;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[11][10], DMG_COLORS[0]);
+
+    ppu.write_byte(0xFF40, 0xF1);
+    ppu.line = 12;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[12][10], DMG_COLORS[1]); //second row of the window and not the third
+  }
+
+  #[test]
+  fn cgb_background_uses_attributes_and_color_palettes()
+  {
+    let mut ppu = Ppu::new(true, HardwareModel::CGB);
+    ppu.write_byte(0xFF40, 0x91); //lcd, tiles at 8000, bg
+
+    ppu.write_byte(0xFF68, 0x80 | (0x08 * 2 + 2)); //palette 2 color 1 with auto increment
+    ppu.write_byte(0xFF69, 0x1F); //red
+    ppu.write_byte(0xFF69, 0x00);
+
+    ppu.write_byte(0xFF4F, 1); //tile 0 in bank 1 has color 1 only in its leftmost pixel
+    ppu.write_byte(0x8000, 0x80);
+    ppu.write_byte(0x9800, 0x08 | 0x20 | 0x02); //bank 1, x flip, palette 2
+
+    ppu.line = 0;
+    ppu.render_line();
+
+    assert_eq!(ppu.read_byte(0xFF68), 0xC0 | (0x08 * 2 + 4));
+    assert_eq!(ppu.screen_buffer[0][0], 0x7FFF);
+    assert_eq!(ppu.screen_buffer[0][7], 0x001F);
+  }
+
+  #[test]
+  fn stat_interrupt_is_requested_on_the_rising_edge_of_the_combined_line()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::CGB);
+    ppu.write_byte(0xFF40, 0x91);
+    ppu.write_byte(0xFF41, 0x28); //mode 0 and mode 2
+
+    ppu.do_ticks(452); //the first line after turning the lcd on stays in mode 0
+    assert!(ppu.irq_stat);
+    assert_eq!(ppu.read_byte(0xFF41), 0xAC); //bit 7, the enabled sources, LY=LYC and mode 0
+    ppu.irq_stat = false;
+
+    ppu.do_ticks(4); //mode 2 of the next line follows mode 0 without a gap
+    assert_eq!(ppu.read_byte(0xFF41) & 0x03, 2);
+    assert!(!ppu.irq_stat);
+
+    ppu.do_ticks(260); //hblank
+    assert!(ppu.irq_stat);
+  }
+
+  #[test]
+  fn ly_reads_0_during_most_of_line_153()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::CGB);
+    ppu.write_byte(0xFF40, 0x91);
+    ppu.write_byte(0xFF45, 0x00);
+    ppu.write_byte(0xFF41, 0x40);
+    ppu.do_ticks(456 * 153);
+    ppu.irq_stat = false;
+
+    assert_eq!(ppu.read_byte(0xFF44), 153);
+    ppu.do_ticks(4);
+    assert_eq!(ppu.read_byte(0xFF44), 0);
+    assert!(!ppu.irq_stat);
+    ppu.do_ticks(8);
+    assert_eq!(ppu.read_byte(0xFF41) & 0x04, 0x04);
+    assert!(ppu.irq_stat);
+  }
+
+  #[test]
+  fn stat_write_requests_an_interrupt_on_the_dmg()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.write_byte(0xFF40, 0x91);
+    ppu.do_ticks(456 * 144 + 4); //vblank
+    ppu.irq_stat = false;
+
+    ppu.write_byte(0xFF41, 0x00);
+    assert!(ppu.irq_stat);
+  }
+
+  #[test]
+  fn only_ten_sprites_per_line_and_lower_x_wins_on_the_dmg()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.write_byte(0xFF40, 0x93); //lcd, tiles at 8000, sprites, bg
+    ppu.write_byte(0xFF47, 0xE4);
+    ppu.write_byte(0xFF48, 0xFF); //OBP0 color 3 everywhere
+    ppu.write_byte(0xFF49, 0x55); //OBP1 color 1 everywhere
+    for row in 0 .. 16 {
+      ppu.write_byte(0x8010 + row, 0xFF); //tile 1 is solid
+    }
+
+    for sprite in 0 .. 11 { //eleven sprites on line 0 - the first one is hidden at X=0 but still counts
+      let address = 0xFE00 + sprite * 4;
+      ppu.write_byte(address, 16);
+      ppu.write_byte(address + 1, (sprite * 8) as u8);
+      ppu.write_byte(address + 2, 1);
+    }
+    ppu.line = 0;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[0][71], DMG_COLORS[3]);
+    assert_eq!(ppu.screen_buffer[0][72], DMG_COLORS[0]);
+
+    ppu.write_byte(0xFE00 + 11 * 4, 24); //the sprite with the lower X wins over the lower OAM index
+    ppu.write_byte(0xFE00 + 11 * 4 + 1, 12);
+    ppu.write_byte(0xFE00 + 11 * 4 + 2, 1);
+    ppu.write_byte(0xFE00 + 12 * 4, 24);
+    ppu.write_byte(0xFE00 + 12 * 4 + 1, 10);
+    ppu.write_byte(0xFE00 + 12 * 4 + 2, 1);
+    ppu.write_byte(0xFE00 + 12 * 4 + 3, 0x10);
+    ppu.line = 8;
+    ppu.render_line();
+    assert_eq!(ppu.screen_buffer[8][3], DMG_COLORS[1]);
+    assert_eq!(ppu.screen_buffer[8][9], DMG_COLORS[1]);
+    assert_eq!(ppu.screen_buffer[8][11], DMG_COLORS[3]);
+  }
+
+  #[test]
+  fn blank_frames_while_the_lcd_is_off_and_the_first_frame_is_skipped()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.write_byte(0xFF47, 0xE4);
+    for row in 0 .. 16 {
+      ppu.write_byte(0x8000 + row, 0xFF); //tile 0 is solid color 3
+    }
+
+    ppu.do_ticks(FRAME_TICKS - 4);
+    assert!(!ppu.frame_ready);
+    ppu.do_ticks(4);
+    assert!(ppu.frame_ready);
+    assert_eq!(ppu.get_frame_buffer()[0], DMG_COLORS[0]);
+    ppu.frame_ready = false;
+
+    ppu.write_byte(0xFF40, 0x91);
+    assert_eq!(ppu.read_byte(0xFF41) & 0x03, 0);
+    ppu.do_ticks(456 * 144);
+    assert!(ppu.frame_ready);
+    assert_eq!(ppu.get_frame_buffer()[0], DMG_COLORS[0]);
+    ppu.frame_ready = false;
+
+    ppu.do_ticks(FRAME_TICKS);
+    assert!(ppu.frame_ready);
+    assert_eq!(ppu.get_frame_buffer()[0], DMG_COLORS[3]);
+
+    ppu.write_byte(0xFF40, 0x11);
+    assert_eq!(ppu.read_byte(0xFF44), 0);
+    assert_eq!(ppu.read_byte(0xFF41) & 0x03, 0);
+  }
+
+  fn mode_3_dots(ppu: &mut Ppu) -> usize {
+    let mut dots = 0;
+    for _ in 0 .. 456 {
+      ppu.do_ticks(1);
+      if ppu.mode == 3 { dots += 1; }
+    }
+    dots
+  }
+
+  //the length of mode 3 on line 1 with the registers and OAM set up by setup
+  fn fifo_line_dots(setup: impl Fn(&mut Ppu)) -> usize {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.set_pixel_fifo(true);
+    setup(&mut ppu);
+    ppu.write_byte(0xFF40, ppu.read_byte(0xFF40) | 0x93);
+    ppu.do_ticks(456); //line 0 after turning the lcd on is different
+    mode_3_dots(&mut ppu)
+  }
+
+  #[test]
+  fn pixel_fifo_mode_3_grows_with_scx_and_sprites()
+  {
+    assert_eq!(fifo_line_dots(|_| ()), 172);
+    assert_eq!(fifo_line_dots(|ppu| ppu.write_byte(0xFF43, 5)), 177); //the first SCX & 7 pixels are dropped
+
+    assert_eq!(fifo_line_dots(|ppu| { //a sprite at the left edge waits for the whole background fetch
+      ppu.write_byte(0xFE00, 17);
+      ppu.write_byte(0xFE01, 8);
+    }), 183);
+
+    assert_eq!(fifo_line_dots(|ppu| { //so does a sprite hidden left of the screen
+      ppu.write_byte(0xFE00, 17);
+      ppu.write_byte(0xFE01, 0);
+    }), 183);
+
+    assert_eq!(fifo_line_dots(|ppu| { //5 pixels into a tile the background fetch is done and only the sprite fetch is left
+      ppu.write_byte(0xFE00, 17);
+      ppu.write_byte(0xFE01, 8 + 13);
+    }), 178);
+
+    assert_eq!(fifo_line_dots(|ppu| { //the window restarts the fetcher in the middle of the line
+      ppu.write_byte(0xFF40, 0x20);
+      ppu.write_byte(0xFF4B, 7 + 80);
+    }), 178);
+
+    assert_eq!(fifo_line_dots(|ppu| { //with WX < 7 the window pixels left of the screen are dropped
+      ppu.write_byte(0xFF40, 0x20);
+      ppu.write_byte(0xFF4B, 3);
+    }), 182);
+
+    assert_eq!(fifo_line_dots(|ppu| { //WX=0 drops SCX & 7 window pixels on top
+      ppu.write_byte(0xFF40, 0x20);
+      ppu.write_byte(0xFF43, 5);
+      ppu.write_byte(0xFF4B, 0);
+    }), 190);
+  }
+
+  #[test]
+  fn pixel_fifo_drops_the_window_pixels_left_of_the_screen()
+  {
+    let line = |pixel_fifo: bool| {
+      let mut ppu = Ppu::new(false, HardwareModel::DMG);
+      ppu.set_pixel_fifo(pixel_fifo);
+      ppu.write_byte(0xFF47, 0xE4); //identity palette
+      ppu.write_byte(0xFF4B, 3); //the first 4 window pixels are left of the screen
+      for row in 0 .. 8 {
+        ppu.write_byte(0x8010 + row * 2, 0x0F); //tile 1 has color 1 in its right half
+      }
+      for tile in 0 .. 32 {
+        ppu.write_byte(0x9C00 + tile, 1);
+      }
+      ppu.write_byte(0xFF40, 0xF1); //lcd, window at 9C00, window, tiles at 8000, bg
+      ppu.do_ticks(2 * 456);
+      ppu.screen_buffer[1]
+    };
+
+    let fifo = line(true);
+    assert_eq!(fifo[0], DMG_COLORS[1]);
+    assert_eq!(fifo[4], DMG_COLORS[0]);
+    assert_eq!(fifo, line(false));
+  }
+
+  #[test]
+  fn pixel_fifo_uses_palette_writes_in_the_middle_of_the_line()
+  {
+    let mut ppu = Ppu::new(false, HardwareModel::DMG);
+    ppu.set_pixel_fifo(true);
+    ppu.write_byte(0xFF40, 0x91);
+    ppu.write_byte(0xFF47, 0xFF);
+    for row in 0 .. 16 {
+      ppu.write_byte(0x8000 + row, 0xFF); //tile 0 is solid color 3
+    }
+
+    ppu.do_ticks(80 + 100); //into mode 3
+    ppu.write_byte(0xFF47, 0x00);
+    ppu.do_ticks(200);
+
+    assert_eq!(ppu.screen_buffer[0][0], DMG_COLORS[3]);
+    assert_eq!(ppu.screen_buffer[0][159], DMG_COLORS[0]);
+  }
+}
+
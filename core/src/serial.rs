@@ -1,29 +1,197 @@
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+use crate::link::LinkCable;
+
+const BIT_TICKS: usize = 512; //the internal clock shifts with 8192Hz
+const FAST_BIT_TICKS: usize = 16; //CGB fast clock with 262144Hz
+
+//gets every byte the game sends - test roms print their results this way
+pub trait SerialSink : Send {
+  fn byte_sent(&mut self, value: u8);
+}
+
+pub struct ConsoleSink;
+
+impl SerialSink for ConsoleSink {
+  fn byte_sent(&mut self, value: u8) {
+    print!("{}", value as char);
+  }
+}
+
+/*
+  SB FF01 - the byte that is shifted out msb first while the byte of the partner is shifted in
+  SC FF02
+    Bit 7 - Transfer Start Flag (0=No transfer is in progress or requested, 1=Transfer in progress, or requested)
+    Bit 1 - Clock Speed (0=Normal, 1=Fast) ** CGB Mode Only **
+    Bit 0 - Shift Clock (0=External Clock, 1=Internal Clock)
+  with the external clock the transfer waits for the partner - without a partner it never finishes
+  with a link cable the partner receives our byte when the transfer starts and its answer is shifted in when it ends
+*/
 pub struct Serial {
-  last_byte_written: u8,
-  other_byte: u8
+  pub irq_serial: bool,
+  data: u8, //SB
+  control: u8, //SC
+  cgb_mode: bool,
+  bits_remaining: usize,
+  clock: usize,
+  sink: Option<Box<dyn SerialSink>>,
+  link: Option<Box<dyn LinkCable>>
 }
 
 impl Serial {
-  pub fn new() -> Serial {
+  pub fn new(cgb_mode: bool) -> Serial {
     Serial {
-      last_byte_written: 0,
-      other_byte: 0
+      irq_serial: false,
+      data: 0,
+      control: 0,
+      cgb_mode,
+      bits_remaining: 0,
+      clock: 0,
+      sink: None,
+      link: None
     }
   }
 
+  pub fn set_link(&mut self, link: Option<Box<dyn LinkCable>>) {
+    self.link = link;
+  }
+
+  pub fn set_sink(&mut self, sink: Option<Box<dyn SerialSink>>) {
+    self.sink = sink;
+  }
+
   pub fn read(&self, address:u16) -> u8 {
     match address {
-      0xFF01 => self.last_byte_written,
-      0xFF02 => self.other_byte,
+      0xFF01 => self.data,
+      0xFF02 => self.control | if self.cgb_mode { 0x7C } else { 0x7E }, //unused bits read 1
       _ => 0
     }
   }
 
   pub fn write(&mut self, address:u16, value: u8) {
     match address {
-      0xFF01 => self.last_byte_written = value,
-      0xFF02 => if value == 0x81 { print!("{}", self.last_byte_written as char); self.other_byte = value; },
+      0xFF01 => self.data = value,
+      0xFF02 => {
+        self.control = value & if self.cgb_mode { 0x83 } else { 0x81 };
+        if self.transfer_requested() {
+          self.bits_remaining = 8;
+          self.clock = 0;
+          if let Some(sink) = self.sink.as_mut() {
+            sink.byte_sent(self.data);
+          }
+          if self.internal_clock() {
+            if let Some(link) = self.link.as_mut() {
+              link.send(self.data);
+            }
+          }
+        } else {
+          self.bits_remaining = 0;
+        }
+      },
       _ => ()
     }
   }
-}
\ No newline at end of file
+
+  pub fn do_ticks(&mut self, ticks: usize) {
+    if let Some(link) = self.link.as_mut() {
+      if let Some(incoming) = link.sync(ticks, self.data) { //the partner clocked a transfer
+        if self.transfer_requested() && !self.internal_clock() {
+          self.data = incoming;
+          self.finish_transfer();
+        }
+      }
+    }
+
+    if !self.transfer_requested() || !self.internal_clock() {
+      return
+    }
+
+    let bit_ticks = if self.control & 0x02 == 0x02 { FAST_BIT_TICKS } else { BIT_TICKS };
+    self.clock += ticks;
+    while self.clock >= bit_ticks && self.bits_remaining > 0 {
+      self.clock -= bit_ticks;
+      self.shift_bit(1); //nothing is connected - the input line is pulled up
+    }
+  }
+
+  fn shift_bit(&mut self, incoming: u8) {
+    self.data = self.data << 1 | incoming;
+    self.bits_remaining -= 1;
+
+    if self.bits_remaining == 0 {
+      if let Some(link) = self.link.as_mut() {
+        self.data = link.receive();
+      }
+      self.finish_transfer();
+    }
+  }
+
+  fn finish_transfer(&mut self) {
+    self.bits_remaining = 0;
+    self.control &= 0x7F;
+    self.irq_serial = true;
+  }
+
+  fn transfer_requested(&self) -> bool {
+    self.control & 0x80 == 0x80
+  }
+
+  fn internal_clock(&self) -> bool {
+    self.control & 0x01 == 0x01
+  }
+}
+
+impl SaveState for Serial {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_u8(self.data);
+    writer.write_u8(self.control);
+    writer.write_usize(self.bits_remaining);
+    writer.write_usize(self.clock);
+    writer.write_bool(self.irq_serial);
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.data = reader.read_u8()?;
+    self.control = reader.read_u8()?;
+    self.bits_remaining = reader.read_usize()?.min(8);
+    self.clock = reader.read_usize()?;
+    self.irq_serial = reader.read_bool()?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn internal_clock_transfer_receives_0xff_without_a_partner()
+  {
+    let mut serial = Serial::new(false);
+    serial.write(0xFF01, 0x42);
+    serial.write(0xFF02, 0x81);
+
+    serial.do_ticks(BIT_TICKS * 8 - 1);
+    assert_eq!(serial.read(0xFF02), 0xFF);
+    assert!(!serial.irq_serial);
+
+    serial.do_ticks(1);
+    assert_eq!(serial.read(0xFF01), 0xFF);
+    assert_eq!(serial.read(0xFF02), 0x7F);
+    assert!(serial.irq_serial);
+  }
+
+  #[test]
+  fn external_clock_waits_for_the_partner()
+  {
+    let mut serial = Serial::new(false);
+    serial.write(0xFF01, 0x42);
+    serial.write(0xFF02, 0x80);
+
+    serial.do_ticks(BIT_TICKS * 16);
+
+    assert_eq!(serial.read(0xFF01), 0x42);
+    assert_eq!(serial.read(0xFF02), 0xFE);
+    assert!(!serial.irq_serial);
+  }
+}
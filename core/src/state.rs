@@ -0,0 +1,242 @@
+use std::fmt;
+use crate::cpu::Cpu;
+
+const STATE_MAGIC: &[u8; 4] = b"RBST";
+const STATE_VERSION: u16 = 1;
+
+#[derive(Debug)]
+pub enum StateError {
+  InvalidFormat, //the data does not start with the magic bytes
+  UnsupportedVersion(u16),
+  WrongRom(String), //the state was created with a different game
+  WrongModel(String), //the state was created on a different hardware model
+  Truncated
+}
+
+impl fmt::Display for StateError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      StateError::InvalidFormat => write!(f, "not a rustboy save state"),
+      StateError::UnsupportedVersion(version) => write!(f, "unsupported save state version {} (expected {})", version, STATE_VERSION),
+      StateError::WrongRom(name) => write!(f, "save state belongs to {}", name),
+      StateError::WrongModel(model) => write!(f, "save state was created on the {}", model),
+      StateError::Truncated => write!(f, "save state is truncated"),
+    }
+  }
+}
+
+impl std::error::Error for StateError {}
+
+pub trait SaveState {
+  fn save_state(&self, writer: &mut StateWriter);
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError>;
+}
+
+/*
+  save state layout
+    4 bytes  magic "RBST"
+    2 bytes  version
+    n bytes  title of the rom and its header and global checksums to detect a wrong game
+    n bytes  the hardware model - the CGB mode follows from it and the rom
+    n bytes  the machine state - every component writes its fields in a fixed order
+*/
+pub fn save(cpu: &Cpu) -> Vec<u8> {
+  let mut writer = StateWriter::new();
+  writer.write_bytes(STATE_MAGIC);
+  writer.write_u16(STATE_VERSION);
+  writer.write_string(&cpu.rom_name());
+  writer.write_u32(cpu.rom_checksum());
+  writer.write_string(&cpu.model().to_string());
+  cpu.save_state(&mut writer);
+  writer.into_bytes()
+}
+
+pub fn load(cpu: &mut Cpu, data: &[u8]) -> Result<(), StateError> {
+  let mut reader = StateReader::new(data);
+
+  let mut magic = [0u8; 4];
+  reader.read_bytes(&mut magic).map_err(|_| StateError::InvalidFormat)?;
+  if &magic != STATE_MAGIC {
+    return Err(StateError::InvalidFormat)
+  }
+
+  let version = reader.read_u16()?;
+  if version != STATE_VERSION {
+    return Err(StateError::UnsupportedVersion(version))
+  }
+
+  let name = reader.read_string()?;
+  if name != cpu.rom_name() || reader.read_u32()? != cpu.rom_checksum() {
+    return Err(StateError::WrongRom(name))
+  }
+
+  let model = reader.read_string()?;
+  if model != cpu.model().to_string() {
+    return Err(StateError::WrongModel(model))
+  }
+
+  let mut backup = StateWriter::new(); //a broken state must not leave a half loaded machine behind
+  cpu.save_state(&mut backup);
+
+  if let Err(error) = cpu.load_state(&mut reader) {
+    cpu.load_state(&mut StateReader::new(&backup.into_bytes())).expect("Failed to restore the machine state!");
+    return Err(error)
+  }
+
+  Ok(())
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+  data: Vec<u8>
+}
+
+impl StateWriter {
+  pub fn new() -> StateWriter {
+    StateWriter {
+      data: vec![]
+    }
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.data
+  }
+
+  pub fn write_u8(&mut self, value: u8) {
+    self.data.push(value);
+  }
+
+  pub fn write_bool(&mut self, value: bool) {
+    self.write_u8(value as u8);
+  }
+
+  pub fn write_u16(&mut self, value: u16) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn write_i16(&mut self, value: i16) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn write_u32(&mut self, value: u32) {
+    self.data.extend_from_slice(&value.to_le_bytes());
+  }
+
+  pub fn write_usize(&mut self, value: usize) {
+    self.data.extend_from_slice(&(value as u64).to_le_bytes());
+  }
+
+  pub fn write_bytes(&mut self, value: &[u8]) {
+    self.data.extend_from_slice(value);
+  }
+
+  pub fn write_string(&mut self, value: &str) {
+    self.write_usize(value.len());
+    self.write_bytes(value.as_bytes());
+  }
+}
+
+pub struct StateReader<'a> {
+  data: &'a [u8],
+  position: usize
+}
+
+impl<'a> StateReader<'a> {
+  pub fn new(data: &'a [u8]) -> StateReader<'a> {
+    StateReader {
+      data,
+      position: 0
+    }
+  }
+
+  pub fn remaining(&self) -> usize {
+    self.data.len() - self.position
+  }
+
+  fn take(&mut self, count: usize) -> Result<&'a [u8], StateError> {
+    if self.remaining() < count {
+      return Err(StateError::Truncated)
+    }
+
+    let result = &self.data[self.position..self.position + count];
+    self.position += count;
+    Ok(result)
+  }
+
+  pub fn read_u8(&mut self) -> Result<u8, StateError> {
+    Ok(self.take(1)?[0])
+  }
+
+  pub fn read_bool(&mut self) -> Result<bool, StateError> {
+    Ok(self.read_u8()? != 0)
+  }
+
+  pub fn read_u16(&mut self) -> Result<u16, StateError> {
+    let mut bytes = [0u8; 2];
+    self.read_bytes(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+  }
+
+  pub fn read_i16(&mut self) -> Result<i16, StateError> {
+    let mut bytes = [0u8; 2];
+    self.read_bytes(&mut bytes)?;
+    Ok(i16::from_le_bytes(bytes))
+  }
+
+  pub fn read_u32(&mut self) -> Result<u32, StateError> {
+    let mut bytes = [0u8; 4];
+    self.read_bytes(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+  }
+
+  pub fn read_usize(&mut self) -> Result<usize, StateError> {
+    let mut bytes = [0u8; 8];
+    self.read_bytes(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes) as usize)
+  }
+
+  pub fn read_bytes(&mut self, target: &mut [u8]) -> Result<(), StateError> {
+    target.copy_from_slice(self.take(target.len())?);
+    Ok(())
+  }
+
+  pub fn read_string(&mut self) -> Result<String, StateError> {
+    let length = self.read_usize()?;
+    Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn values_survive_a_round_trip()
+  {
+    let mut writer = StateWriter::new();
+    writer.write_u8(0x12);
+    writer.write_bool(true);
+    writer.write_u16(0x3456);
+    writer.write_i16(-7);
+    writer.write_usize(0x1234_5678);
+    writer.write_string("TETRIS");
+    let data = writer.into_bytes();
+
+    let mut reader = StateReader::new(&data);
+    assert_eq!(reader.read_u8().unwrap(), 0x12);
+    assert!(reader.read_bool().unwrap());
+    assert_eq!(reader.read_u16().unwrap(), 0x3456);
+    assert_eq!(reader.read_i16().unwrap(), -7);
+    assert_eq!(reader.read_usize().unwrap(), 0x1234_5678);
+    assert_eq!(reader.read_string().unwrap(), "TETRIS");
+    assert_eq!(reader.remaining(), 0);
+  }
+
+  #[test]
+  fn reading_past_the_end_is_an_error()
+  {
+    let mut reader = StateReader::new(&[0x01]);
+    assert!(reader.read_u16().is_err());
+  }
+}
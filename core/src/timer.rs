@@ -1,14 +1,28 @@
+use crate::state::{SaveState, StateWriter, StateReader, StateError};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Reload {
+  Idle,
+  Pending, //TIMA overflowed and reads 0 - a write to TIMA cancels the reload
+  Reloading //TIMA was just loaded from TMA - writes to TIMA are ignored and writes to TMA go to TIMA as well
+}
+
+/*
+  the timer is driven by the 16 bit divider that counts every tick - DIV is its upper byte.
+  TAC selects a bit of the divider and TIMA increments when that bit AND the enable flag goes from 1 to 0.
+  so resetting DIV or changing TAC can increment TIMA as well.
+  an overflow leaves TIMA at 0 for one M-cycle before it is reloaded from TMA and the interrupt is requested.
+*/
 pub struct Timer {
   pub irq_timer: bool,
 
-  divide: u8,
-  divide_ticks: usize,
+  divider: u16,
 
-  timer_counter: u8,
-  timer_ticks: usize,
-  timer_modulo: u8,
+  timer_counter: u8, //TIMA
+  timer_modulo: u8, //TMA
   timer_enabled: bool,
-  timer_steps: usize
+  timer_bit: u16, //the divider bit selected by TAC
+  reload: Reload
 }
 
 impl Timer {
@@ -16,28 +30,37 @@ impl Timer {
     Timer {
       irq_timer: false,
 
-      divide: 0,
-      divide_ticks: 0,
+      divider: 0,
 
       timer_counter: 0,
-      timer_ticks: 0,
       timer_modulo: 0,
       timer_enabled: false,
-      timer_steps: 256
+      timer_bit: 0x0200,
+      reload: Reload::Idle
     }
   }
 
+  pub fn set_divider(&mut self, value: u16) {
+    self.divider = value;
+  }
+
+  //the internal divider - the APU frame sequencer is clocked by it as well
+  pub fn divider(&self) -> u16 {
+    self.divider
+  }
+
   pub fn read_byte(&self, address: u16) -> u8 {
     match address {
-      0xFF04 => self.divide,
+      0xFF04 => (self.divider >> 8) as u8,
       0xFF05 => self.timer_counter,
       0xFF06 => self.timer_modulo,
       0xFF07 => {
+        0xF8 | //unused bits read 1
         (if self.timer_enabled { 0x04 } else { 0x0 }) |
-        (match self.timer_steps {
-          1024 => 0x00,
-          16 => 0x01,
-          64 => 0x02,
+        (match self.timer_bit {
+          0x0200 => 0x00,
+          0x0008 => 0x01,
+          0x0020 => 0x02,
           _ => 0x03
         })
       },
@@ -47,44 +70,169 @@ impl Timer {
 
   pub fn write_byte(&mut self, address: u16, value: u8) {
     match address {
-      0xFF04 => self.divide = 0x00, //reset on any write
-      0xFF05 => self.timer_counter = value,
-      0xFF06 => self.timer_modulo = value,
+      0xFF04 => { //reset on any write
+        let input = self.timer_input();
+        self.divider = 0;
+        self.check_falling_edge(input);
+      },
+      0xFF05 => match self.reload {
+        Reload::Pending => { self.timer_counter = value; self.reload = Reload::Idle; },
+        Reload::Reloading => (),
+        Reload::Idle => self.timer_counter = value
+      },
+      0xFF06 => {
+        self.timer_modulo = value;
+        if self.reload == Reload::Reloading {
+          self.timer_counter = value;
+        }
+      },
       0xFF07 => {
-        self.timer_enabled = (value & 0x04) == 0x04; //bit 2 is enabled yes/no
-        self.timer_steps = match value & 0x03 {
-          0 => 1024,
-          1 => 16,
-          2 => 64,
-          _ => 256
-        }; // the two lowest bits are the mode
+        let input = self.timer_input();
+        self.set_control(value);
+        self.check_falling_edge(input);
       },
       _ => { println!("Write to unmapped timer address: {:#06X}", address); }
     }
   }
 
+  fn set_control(&mut self, value: u8) {
+    self.timer_enabled = (value & 0x04) == 0x04; //bit 2 is enabled yes/no
+    self.timer_bit = match value & 0x03 {
+      0 => 0x0200,
+      1 => 0x0008,
+      2 => 0x0020,
+      _ => 0x0080
+    }; // the two lowest bits are the mode
+  }
+
+  fn timer_input(&self) -> bool {
+    self.timer_enabled && self.divider & self.timer_bit != 0
+  }
+
+  fn check_falling_edge(&mut self, input: bool) {
+    if input && !self.timer_input() {
+      self.increment_counter();
+    }
+  }
+
+  fn increment_counter(&mut self) {
+    if self.timer_counter == 0xFF {
+      self.timer_counter = 0;
+      self.reload = Reload::Pending;
+    } else {
+      self.timer_counter += 1;
+    }
+  }
+
   /*
-    00: CPU Clock / 1024 (DMG, CGB:   4096 Hz, SGB:   ~4194 Hz)
-    01: CPU Clock / 16   (DMG, CGB: 262144 Hz, SGB: ~268400 Hz)
-    10: CPU Clock / 64   (DMG, CGB:  65536 Hz, SGB:  ~67110 Hz)
-    11: CPU Clock / 256  (DMG, CGB:  16384 Hz, SGB:  ~16780 Hz)
+    00: CPU Clock / 1024 (DMG, CGB:   4096 Hz, SGB:   ~4194 Hz) - divider bit 9
+    01: CPU Clock / 16   (DMG, CGB: 262144 Hz, SGB: ~268400 Hz) - divider bit 3
+    10: CPU Clock / 64   (DMG, CGB:  65536 Hz, SGB:  ~67110 Hz) - divider bit 5
+    11: CPU Clock / 256  (DMG, CGB:  16384 Hz, SGB:  ~16780 Hz) - divider bit 7
   */
   pub fn do_ticks(&mut self, ticks: usize) {
-    self.divide_ticks += ticks;
-    while self.divide_ticks >= 256 {
-      self.divide_ticks -= 256;
-      self.divide = self.divide.wrapping_add(1);
-    }
+    let mut ticks = ticks;
+    while ticks > 0 { //one M-cycle at a time - the selected bit can't fall twice in it
+      let step = ticks.min(4);
+      ticks -= step;
 
-    if self.timer_enabled {
-      self.timer_ticks += ticks;
-      while self.timer_ticks >= self.timer_steps {
-        self.timer_ticks -= self.timer_steps;
-        self.timer_counter = match self.timer_counter {
-          0xFF => { self.irq_timer = true;  self.timer_modulo },
-          _ => self.timer_counter + 1
-        };
+      match self.reload {
+        Reload::Pending => {
+          self.timer_counter = self.timer_modulo;
+          self.irq_timer = true;
+          self.reload = Reload::Reloading;
+        },
+        Reload::Reloading => self.reload = Reload::Idle,
+        Reload::Idle => ()
       }
+
+      let input = self.timer_input();
+      self.divider = self.divider.wrapping_add(step as u16);
+      self.check_falling_edge(input);
     }
   }
-}
\ No newline at end of file
+}
+
+impl SaveState for Timer {
+  fn save_state(&self, writer: &mut StateWriter) {
+    writer.write_bool(self.irq_timer);
+    writer.write_u16(self.divider);
+    writer.write_u8(self.timer_counter);
+    writer.write_u8(self.timer_modulo);
+    writer.write_u8(self.read_byte(0xFF07));
+    writer.write_u8(match self.reload {
+      Reload::Idle => 0,
+      Reload::Pending => 1,
+      Reload::Reloading => 2
+    });
+  }
+
+  fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+    self.irq_timer = reader.read_bool()?;
+    self.divider = reader.read_u16()?;
+    self.timer_counter = reader.read_u8()?;
+    self.timer_modulo = reader.read_u8()?;
+    self.set_control(reader.read_u8()?);
+    self.reload = match reader.read_u8()? {
+      1 => Reload::Pending,
+      2 => Reload::Reloading,
+      _ => Reload::Idle
+    };
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test
+{
+  use super::*;
+
+  #[test]
+  fn tima_increments_on_the_falling_edge_of_the_divider_bit()
+  {
+    let mut timer = Timer::new();
+    timer.write_byte(0xFF07, 0x05); //bit 3
+    timer.do_ticks(16);
+    assert_eq!(timer.read_byte(0xFF05), 1);
+
+    timer.do_ticks(8); //bit 3 is set
+    timer.write_byte(0xFF04, 0x00); //resetting DIV lets it fall
+    assert_eq!(timer.read_byte(0xFF05), 2);
+    assert_eq!(timer.read_byte(0xFF04), 0);
+
+    timer.do_ticks(8);
+    timer.write_byte(0xFF07, 0x00); //disabling the timer with the bit set increments as well
+    assert_eq!(timer.read_byte(0xFF05), 3);
+    assert_eq!(timer.read_byte(0xFF07), 0xF8);
+  }
+
+  #[test]
+  fn tima_is_reloaded_one_m_cycle_after_the_overflow()
+  {
+    let mut timer = Timer::new();
+    timer.write_byte(0xFF06, 0x42);
+    timer.write_byte(0xFF05, 0xFF);
+    timer.write_byte(0xFF07, 0x05);
+
+    timer.do_ticks(16);
+    assert_eq!(timer.read_byte(0xFF05), 0x00);
+    assert!(!timer.irq_timer);
+    timer.do_ticks(4);
+    assert_eq!(timer.read_byte(0xFF05), 0x42);
+    assert!(timer.irq_timer);
+
+    timer.write_byte(0xFF05, 0x10); //ignored in the cycle of the reload
+    timer.write_byte(0xFF06, 0x50); //but TMA goes straight to TIMA
+    assert_eq!(timer.read_byte(0xFF05), 0x50);
+
+    timer.do_ticks(4);
+    timer.irq_timer = false;
+    timer.write_byte(0xFF05, 0xFF);
+    timer.do_ticks(8);
+    assert_eq!(timer.read_byte(0xFF05), 0x00);
+    timer.write_byte(0xFF05, 0x20); //writing TIMA before the reload cancels it
+    timer.do_ticks(4);
+    assert_eq!(timer.read_byte(0xFF05), 0x20);
+    assert!(!timer.irq_timer);
+  }
+}
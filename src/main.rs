@@ -1,55 +1,224 @@
 mod sdl;
+mod options;
 
 use sdl::init_hardware;
-use std::sync::mpsc;
-use std::sync::mpsc::TryRecvError;
-use std::thread;
+use sdl::input::Command;
 use std::thread::sleep;
 use std::time::Duration;
 
 use core::*;
-use core::cpu::Cpu;
-use core::mbc::load_rom;
+use core::emulator::Emulator;
+use core::mbc::{load_rom, load_boot_rom, header_warning};
+use core::serial::ConsoleSink;
+use core::link;
+use core::link::LinkCable;
+use core::model::HardwareModel;
+use options::{Options, LinkOption, USAGE};
 use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const SAVE_INTERVAL: usize = 300; //frames between checks if the battery ram has to be written - about 5 seconds
 
 fn main() {
-  let args: Vec<String> = env::args().collect();
-  let rom = load_rom(&args[1]);
+  let args: Vec<String> = env::args().skip(1).collect();
+  let options = match Options::parse(&args) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{}\n\n{}", error, USAGE);
+      process::exit(1);
+    }
+  };
+
+  let mut rom = match load_rom(&options.rom_file) {
+    Ok(rom) => rom,
+    Err(error) => {
+      eprintln!("Failed to load {}: {}", options.rom_file, error);
+      process::exit(1);
+    }
+  };
   println!("Successfully loaded: {}", rom.name());
+  println!("{}", rom.header());
+  if let Some(warning) = header_warning(rom.header()) {
+    println!("Warning: {}", warning);
+  }
 
-  let (video_sender, video_receiver) = mpsc::channel::<Vec<u8>>();
-  let (audio_sender, audio_receiver) = mpsc::channel::<Vec<i16>>();
-  let (input_sender, input_receiver) = mpsc::channel::<GBEvent>();
+  let save_file = Path::new(&options.rom_file).with_extension("sav");
+  if let Ok(save_data) = fs::read(&save_file) {
+    rom.load_save_data(&save_data);
+    println!("Loaded save file: {}", save_file.display());
+  }
 
-  let (mut input, mut display, mut sound) = init_hardware(2 * SCREEN_WIDTH as u32, 2 * SCREEN_HEIGHT as u32, input_sender);
+  let boot_rom = match &options.boot_rom_file {
+    Some(file) => match load_boot_rom(file) {
+      Ok(boot_rom) => Some(boot_rom),
+      Err(error) => {
+        eprintln!("Failed to load the boot rom {}: {}", file, error);
+        process::exit(1);
+      }
+    },
+    None => None
+  };
 
-  let cpu = Cpu::new(rom, video_sender, audio_sender);
-  let cpu_thread = thread::Builder::new().name("cpu".to_string()).spawn(move|| main_loop(cpu, input_receiver)).unwrap();
+  let model = options.model.unwrap_or_else(|| HardwareModel::for_header(rom.header()));
+  println!("Hardware model:  {}", model);
+
+  let mut emulator = Emulator::new(rom, boot_rom, model);
+  if options.serial_console { //test roms print their results over the serial port
+    emulator.set_serial_sink(Some(Box::new(ConsoleSink)));
+  }
+  emulator.set_log_access_violations(options.log_access_violations);
+  emulator.set_pixel_fifo(options.pixel_fifo);
+  emulator.set_sample_rate(options.sample_rate);
+
+  if let Some(link) = &options.link {
+    match connect_link(link) {
+      Ok(cable) => emulator.set_link_cable(Some(cable)),
+      Err(error) => {
+        eprintln!("Failed to connect the link cable: {}", error);
+        process::exit(1);
+      }
+    }
+  }
+
+  let mut battery = Battery::new(save_file);
+
+  if options.headless {
+    run_headless(&mut emulator, &mut battery, options.frames);
+  } else {
+    run(&mut emulator, &mut battery, &options);
+  }
+
+  battery.write(&emulator); //always write the save when the emulator quits
+}
+
+fn run(emulator: &mut Emulator, battery: &mut Battery, options: &Options) {
+  let (mut input, mut display, mut sound) = init_hardware(2 * SCREEN_WIDTH as u32, 2 * SCREEN_HEIGHT as u32, options.sample_rate, options.audio_buffer_size);
+  if sound.sample_rate() != options.sample_rate || sound.buffer_size() != options.audio_buffer_size {
+    println!("Audio output:    {} Hz, {} samples", sound.sample_rate(), sound.buffer_size());
+  }
+  emulator.set_sample_rate(sound.sample_rate());
 
   sound.play();
 
-  while input.process_input() {
-    match video_receiver.try_recv() {
-      Ok(screen_buffer) => display.draw_screen(screen_buffer),
-      Err(TryRecvError::Disconnected) => break,
-      Err(TryRecvError::Empty) => ()
+  let mut frames = 0;
+  'running: while options.frames.is_none_or(|limit| frames < limit) {
+    for command in input.process_input() {
+      match command {
+        Command::SaveState(slot) => save_state(emulator, &state_file(&options.rom_file, slot)),
+        Command::LoadState(slot) => load_state(emulator, &state_file(&options.rom_file, slot)),
+        Command::Quit => break 'running
+      }
+    }
+
+    emulator.set_buttons(input.buttons());
+
+    let output = emulator.run_frame();
+    display.draw_screen(output.frame);
+    sound.queue(output.audio);
+
+    battery.update(emulator);
+    frames += 1;
+
+    while sound.queue_size() > sound.buffer_size() { //the audio device sets the pace of the emulation - at most one buffer waits to be played
+      sleep(Duration::from_millis(1));
+    }
+  }
+
+  sound.stop();
+}
+
+//runs as fast as possible - with a link cable the partner sets the pace
+fn run_headless(emulator: &mut Emulator, battery: &mut Battery, frames: Option<usize>) {
+  let mut frame = 0;
+  while frames.is_none_or(|limit| frame < limit) {
+    emulator.run_frame();
+    battery.update(emulator);
+    frame += 1;
+  }
+}
+
+fn connect_link(link: &LinkOption) -> io::Result<Box<dyn LinkCable>> {
+  match link {
+    LinkOption::Host(address) => {
+      println!("Waiting for the link cable partner on {}", address);
+      #[cfg(unix)]
+      {
+        if let Some(path) = address.strip_prefix("unix:") {
+          return Ok(Box::new(link::host_unix(path)?))
+        }
+      }
+      Ok(Box::new(link::host(address.as_str())?))
+    },
+    LinkOption::Connect(address) => {
+      #[cfg(unix)]
+      {
+        if let Some(path) = address.strip_prefix("unix:") {
+          return Ok(Box::new(link::connect_unix(path)?))
+        }
+      }
+      Ok(Box::new(link::connect(address.as_str())?))
     }
+  }
+}
+
+//writes the battery ram to the .sav file when it has changed
+struct Battery {
+  save_file: PathBuf,
+  frames_since_save: usize
+}
 
-    match audio_receiver.try_recv() {
-      Ok(sound_buffer) => sound.queue(sound_buffer),
-      Err(TryRecvError::Disconnected) => break,
-      Err(TryRecvError::Empty) => ()
+impl Battery {
+  fn new(save_file: PathBuf) -> Battery {
+    Battery {
+      save_file,
+      frames_since_save: 0
     }
+  }
 
-    if sound.queue_size() <= AUDIO_BUFFER_SIZE {
-      cpu_thread.thread().unpark();
+  fn update(&mut self, emulator: &mut Emulator) {
+    self.frames_since_save += 1;
+    if self.frames_since_save >= SAVE_INTERVAL { //only write the battery ram when the game has written to it
+      if emulator.save_data_changed() {
+        self.write(emulator);
+      }
+      self.frames_since_save = 0;
     }
+  }
 
-    sleep(Duration::from_millis(1));  //sleep for a ms to not have this thread run at 100% cpu
+  fn write(&self, emulator: &Emulator) {
+    if let Some(save_data) = emulator.save_data() {
+      write_save_file(&self.save_file, &save_data);
+    }
   }
+}
 
-  sound.stop();
+fn write_save_file(save_file: &Path, save_data: &[u8]) {
+  if let Err(error) = fs::write(save_file, save_data) {
+    println!("Failed to write save file {}: {}", save_file.display(), error);
+  }
+}
+
+fn state_file(rom_file: &str, slot: usize) -> PathBuf { //the slots are stored next to the rom as .ss0 - .ss9
+  Path::new(rom_file).with_extension(format!("ss{}", slot))
+}
+
+fn save_state(emulator: &Emulator, file: &Path) {
+  match fs::write(file, emulator.save_state()) {
+    Ok(_) => println!("Saved state to {}", file.display()),
+    Err(error) => println!("Failed to write state {}: {}", file.display(), error)
+  }
+}
+
+fn load_state(emulator: &mut Emulator, file: &Path) {
+  let result = fs::read(file)
+    .map_err(|error| error.to_string())
+    .and_then(|data| emulator.load_state(&data).map_err(|error| error.to_string()));
 
-  cpu_thread.thread().unpark(); // unpark if we exit while the cpu thread is parked
-  cpu_thread.join().expect("failed to end cpu thread!");
+  match result {
+    Ok(_) => println!("Loaded state from {}", file.display()),
+    Err(error) => println!("Failed to load state {}: {}", file.display(), error)
+  }
 }
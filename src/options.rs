@@ -0,0 +1,106 @@
+pub const USAGE: &str = "usage: rustboy [options] <rom>
+
+options:
+  --boot-rom <file>       run a DMG or CGB boot rom before the game
+  --model <model>         the emulated hardware: DMG0, DMG, MGB, SGB, SGB2, CGB or AGB
+                          defaults to CGB for color games and DMG for the others
+  --serial-console        print the bytes the game sends over the serial port
+  --log-access-violations print vram, OAM and palette accesses the ppu blocks
+  --pixel-fifo            draw every dot with the pixel fifo for mid-scanline effects
+  --link-host <address>   wait for a second rustboy to connect a link cable
+  --link-connect <address>
+                          connect a link cable to a waiting rustboy
+                          addresses are host:port - unix:<path> uses a unix domain socket
+  --sample-rate <hz>      the audio sample rate - 48000 by default
+  --audio-buffer <samples>
+                          the samples the audio device plays at once - smaller buffers lower the latency
+  --headless              run without window and sound
+  --frames <count>        stop after the given number of frames";
+
+use core::model::HardwareModel;
+use core::{AUDIO_OUTPUT_FREQUENCY, AUDIO_BUFFER_SIZE};
+
+pub enum LinkOption {
+  Host(String),
+  Connect(String)
+}
+
+pub struct Options {
+  pub rom_file: String,
+  pub boot_rom_file: Option<String>,
+  pub model: Option<HardwareModel>,
+  pub serial_console: bool,
+  pub log_access_violations: bool,
+  pub pixel_fifo: bool,
+  pub sample_rate: usize,
+  pub audio_buffer_size: usize,
+  pub link: Option<LinkOption>,
+  pub headless: bool,
+  pub frames: Option<usize>
+}
+
+impl Options {
+  //the arguments without the program name
+  pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut rom_file = None;
+    let mut boot_rom_file = None;
+    let mut model = None;
+    let mut serial_console = false;
+    let mut log_access_violations = false;
+    let mut pixel_fifo = false;
+    let mut sample_rate = AUDIO_OUTPUT_FREQUENCY;
+    let mut audio_buffer_size = AUDIO_BUFFER_SIZE;
+    let mut link = None;
+    let mut headless = false;
+    let mut frames = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--boot-rom" => boot_rom_file = Some(value(&mut args, arg)?),
+        "--model" => model = Some(value(&mut args, arg)?.parse()?),
+        "--serial-console" => serial_console = true,
+        "--log-access-violations" => log_access_violations = true,
+        "--pixel-fifo" => pixel_fifo = true,
+        "--sample-rate" => sample_rate = number(&mut args, arg, 8_000 ..= 192_000)?,
+        "--audio-buffer" => audio_buffer_size = number(&mut args, arg, 16 ..= 32_768)?,
+        "--link-host" => link = Some(LinkOption::Host(value(&mut args, arg)?)),
+        "--link-connect" => link = Some(LinkOption::Connect(value(&mut args, arg)?)),
+        "--headless" => headless = true,
+        "--frames" => frames = Some(value(&mut args, arg)?.parse().map_err(|_| "--frames expects a number".to_string())?),
+        option if option.starts_with("--") => return Err(format!("unknown option {}", option)),
+        file => {
+          if rom_file.is_some() {
+            return Err(format!("more than one rom given: {}", file))
+          }
+          rom_file = Some(file.to_string());
+        }
+      }
+    }
+
+    Ok(Options {
+      rom_file: rom_file.ok_or_else(|| "no rom given".to_string())?,
+      boot_rom_file,
+      model,
+      serial_console,
+      log_access_violations,
+      pixel_fifo,
+      sample_rate,
+      audio_buffer_size,
+      link,
+      headless,
+      frames
+    })
+  }
+}
+
+fn value(args: &mut std::slice::Iter<String>, option: &str) -> Result<String, String> {
+  args.next().cloned().ok_or_else(|| format!("{} expects a value", option))
+}
+
+fn number(args: &mut std::slice::Iter<String>, option: &str, range: std::ops::RangeInclusive<usize>) -> Result<usize, String> {
+  match value(args, option)?.parse() {
+    Ok(number) if range.contains(&number) => Ok(number),
+    _ => Err(format!("{} expects a number from {} to {}", option, range.start(), range.end()))
+  }
+}
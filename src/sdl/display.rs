@@ -33,7 +33,7 @@ impl Display {
     }
   }
 
-  pub fn draw_screen(&mut self, screen_buffer: Vec<u8>) {
+  pub fn draw_screen(&mut self, screen_buffer: &[u16]) {
     self.canvas.set_draw_color(Color::RGB(0, 0, 0));
     self.canvas.clear();
 
@@ -46,11 +46,11 @@ impl Display {
   }
 }
 
-fn map_color(color: u8) -> Color {
-  match color {
-    0 => Color::RGB( 0xE0, 0xF8, 0xD0),
-    1 => Color::RGB( 0x88, 0xC0, 0x70),
-    2 => Color::RGB( 0x34, 0x68, 0x56),
-    _ => Color::RGB( 0x08, 0x18, 0x20)
-  }
+//15 bit colors - bits 0-4 red, 5-9 green, 10-14 blue
+fn map_color(color: u16) -> Color {
+  let channel = |shift: u16| {
+    let value = ((color >> shift) & 0x1F) as u8;
+    value << 3 | value >> 2
+  };
+  Color::RGB(channel(0), channel(5), channel(10))
 }
\ No newline at end of file
@@ -1,54 +1,89 @@
-
 use sdl2::event::Event;
 use sdl2::EventPump;
 use sdl2::keyboard::Keycode;
 use sdl2::Sdl;
-use core::GBEvent;
-use std::sync::mpsc::Sender;
-use core::GBKeyEvent;
-use core::GBKeyState;
 use core::GBKeyCode;
 
+pub enum Command {
+  SaveState(usize), //slot number
+  LoadState(usize),
+  Quit
+}
+
 pub struct Input {
   event_pump: EventPump,
-  input_sender: Sender<GBEvent>
+  buttons: [bool; 8], //indexed by GBKeyCode
+  state_slot: usize
 }
 
 impl Input {
-  pub fn new(sdl: &Sdl, input_sender: Sender<GBEvent>) -> Input {
+  pub fn new(sdl: &Sdl) -> Input {
     Input {
       event_pump: sdl.event_pump().unwrap(),
-      input_sender
+      buttons: [false; 8],
+      state_slot: 1
     }
   }
 
-  pub fn process_input(&mut self) -> bool {
+  pub fn buttons(&self) -> [bool; 8] {
+    self.buttons
+  }
+
+  pub fn process_input(&mut self) -> Vec<Command> {
+    let mut commands = vec![];
+
     for event in self.event_pump.poll_iter() {
       match event {
-        Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => { //match keydown escape first so it has priority
-          self.input_sender.send(GBEvent::Quit).expect("failed to send input to emulator");
-          return false
+        Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => commands.push(Command::Quit), //match keydown escape first so it has priority
+        Event::KeyDown { keycode:Some(Keycode::F5), .. } => commands.push(Command::SaveState(self.state_slot)),
+        Event::KeyDown { keycode:Some(Keycode::F8), .. } => commands.push(Command::LoadState(self.state_slot)),
+        Event::KeyDown { keycode:Some(keycode), .. } => {
+          if let Some(button) = button(keycode) {
+            self.buttons[button as usize] = true;
+          } else if let Some(slot) = state_slot(keycode) {
+            self.state_slot = slot;
+            println!("Selected state slot {}", slot);
+          }
+        },
+        Event::KeyUp { keycode:Some(keycode), .. } => {
+          if let Some(button) = button(keycode) {
+            self.buttons[button as usize] = false;
+          }
         },
-        Event::KeyUp { keycode:Some(Keycode::Up), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Up })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Up), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Up })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::Down), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Down })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Down), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Down })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::Left), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Left })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Left), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Left })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::Right), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Right })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Right), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Right })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::A), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::A })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::A), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::A })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::S), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::B })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::S), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::B })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::Space), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Select })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Space), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Select })).unwrap(),
-        Event::KeyUp { keycode:Some(Keycode::Return), .. } =>  self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyUp, key_code: GBKeyCode::Start })).unwrap(),
-        Event::KeyDown { keycode:Some(Keycode::Return), .. } => self.input_sender.send(GBEvent::KeyEvent(GBKeyEvent { state: GBKeyState::KeyDown, key_code: GBKeyCode::Start })).unwrap(),
         _ => {}
       }
     }
 
-    true
+    commands
   }
-}
\ No newline at end of file
+}
+
+fn button(keycode: Keycode) -> Option<GBKeyCode> {
+  match keycode {
+    Keycode::Up => Some(GBKeyCode::Up),
+    Keycode::Down => Some(GBKeyCode::Down),
+    Keycode::Left => Some(GBKeyCode::Left),
+    Keycode::Right => Some(GBKeyCode::Right),
+    Keycode::A => Some(GBKeyCode::A),
+    Keycode::S => Some(GBKeyCode::B),
+    Keycode::Space => Some(GBKeyCode::Select),
+    Keycode::Return => Some(GBKeyCode::Start),
+    _ => None
+  }
+}
+
+fn state_slot(keycode: Keycode) -> Option<usize> {
+  match keycode {
+    Keycode::Num0 => Some(0),
+    Keycode::Num1 => Some(1),
+    Keycode::Num2 => Some(2),
+    Keycode::Num3 => Some(3),
+    Keycode::Num4 => Some(4),
+    Keycode::Num5 => Some(5),
+    Keycode::Num6 => Some(6),
+    Keycode::Num7 => Some(7),
+    Keycode::Num8 => Some(8),
+    Keycode::Num9 => Some(9),
+    _ => None
+  }
+}
@@ -1,19 +1,17 @@
-mod input;
+pub mod input;
 mod display;
 mod sound;
 
-use std::sync::mpsc::Sender;
-use core::GBEvent;
 use crate::sdl::input::Input;
 use crate::sdl::display::Display;
 use crate::sdl::sound::Sound;
 
-pub fn init_hardware(width:u32, height: u32, input_sender: Sender<GBEvent>) -> (Input, Display, Sound) {
+pub fn init_hardware(width:u32, height: u32, sample_rate: usize, buffer_size: usize) -> (Input, Display, Sound) {
   let sdl_context = sdl2::init().expect("Failed to init SDL2!");
 
   (
-    Input::new(&sdl_context, input_sender),
+    Input::new(&sdl_context),
     Display::new(&sdl_context, width, height),
-    Sound::new(&sdl_context),
+    Sound::new(&sdl_context, sample_rate, buffer_size),
   )
-}
\ No newline at end of file
+}
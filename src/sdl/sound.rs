@@ -1,80 +1,113 @@
-use sdl2::audio::*;
-use sdl2::Sdl;
-use core::AUDIO_OUTPUT_FREQUENCY;
-use core::AUDIO_BUFFER_SIZE;
-use std::collections::vec_deque::VecDeque;
-
-struct SoundBuffer {
-  queue: VecDeque<i16>
+use sdl2::{AudioSubsystem, Sdl};
+use sdl2::sys;
+use std::mem;
+use std::ptr;
+
+const CHANNELS: usize = 2; //stereo
+
+/*
+  the audio is queued to the device instead of pulled by a callback - the device plays what the emulator pushed.
+  we let the device choose the rate and the buffer size and the emulator produces the samples at that rate.
+*/
+pub struct Sound {
+  device: QueueDevice,
+  sample_rate: usize,
+  buffer_size: usize
 }
 
-impl SoundBuffer {
-  pub fn new() -> SoundBuffer {
-    SoundBuffer {
-      queue: VecDeque::new()
+impl Sound {
+  pub fn new(sdl: &Sdl, sample_rate: usize, buffer_size: usize) -> Sound {
+    let audio_subsystem = sdl.audio().unwrap();
+    let (device, obtained_spec) = QueueDevice::open(audio_subsystem, sample_rate, buffer_size)
+      .unwrap_or_else(|error| panic!("Could not open the audio device: {}", error));
+
+    Sound {
+      device,
+      sample_rate: obtained_spec.freq as usize,
+      buffer_size: obtained_spec.samples as usize
     }
   }
 
-  pub fn queue(&mut self, data: Vec<i16>) {
-    self.queue.append(&mut VecDeque::from(data));
+  pub fn sample_rate(&self) -> usize {
+    self.sample_rate
   }
-}
 
-impl AudioCallback for SoundBuffer {
-  type Channel = i16;
+  //samples per channel the device plays at once
+  pub fn buffer_size(&self) -> usize {
+    self.buffer_size
+  }
 
-  fn callback(&mut self, out: &mut [i16]) {
-    for x in out.iter_mut() {
-      *x = match self.queue.pop_front() {
-        Some(x) => x,
-        None => { /*println!("Audio buffer underflow!");*/ 0 }
-      };
-    }
+  pub fn queue(&mut self, data: &[i16]) {
+    self.device.queue(data);
   }
-}
 
-pub struct Sound {
-  device: AudioDevice<SoundBuffer>
+  //samples per channel waiting to be played
+  pub fn queue_size(&mut self) -> usize {
+    self.device.queued_bytes() / mem::size_of::<i16>() / CHANNELS
+  }
+
+  pub fn play(&mut self) {
+    self.device.pause(false);
+  }
+
+  pub fn stop(&mut self) {
+    self.device.pause(true);
+  }
 }
 
-impl Sound {
-  pub fn new(sdl: &Sdl) -> Sound {
-    let audio_subsystem = sdl.audio().unwrap();
+/*
+  an open SDL audio device that is fed with SDL_QueueAudio - closed when it is dropped.
+  AudioQueue of the sdl2 crate opens the device without allowed changes so SDL would resample to the desired rate instead of telling us the rate of the hardware.
+*/
+struct QueueDevice {
+  _audio_subsystem: AudioSubsystem, //SDL must not shut the audio down while the device is open
+  id: sys::SDL_AudioDeviceID
+}
 
-    let desired_spec = AudioSpecDesired {
-      freq: Some(AUDIO_OUTPUT_FREQUENCY as i32),
-      channels: Some(2), // stereo
-      samples: Some(AUDIO_BUFFER_SIZE as u16),
+impl QueueDevice {
+  fn open(audio_subsystem: AudioSubsystem, sample_rate: usize, buffer_size: usize) -> Result<(QueueDevice, sys::SDL_AudioSpec), String> {
+    let desired_spec = sys::SDL_AudioSpec {
+      freq: sample_rate as i32,
+      format: sys::AUDIO_S16SYS as u16,
+      channels: CHANNELS as u8,
+      silence: 0,
+      samples: buffer_size as u16,
+      padding: 0,
+      size: 0,
+      callback: None, //no callback selects the queue
+      userdata: ptr::null_mut()
     };
+    let mut obtained_spec = desired_spec;
 
-    let device = audio_subsystem.open_playback(None, &desired_spec, | _spec | {
-      SoundBuffer::new()
-    }).unwrap();
-
-    Sound {
-      device,
+    let allowed_changes = sys::SDL_AUDIO_ALLOW_FREQUENCY_CHANGE | sys::SDL_AUDIO_ALLOW_SAMPLES_CHANGE;
+    // SAFETY: both specs are valid for the whole call, the default device is selected by the null name and SDL only writes to obtained_spec
+    let id = unsafe { sys::SDL_OpenAudioDevice(ptr::null(), 0, &desired_spec, &mut obtained_spec, allowed_changes as i32) };
+    if id == 0 {
+      return Err(sdl2::get_error())
     }
+
+    Ok((QueueDevice { _audio_subsystem: audio_subsystem, id }, obtained_spec))
   }
 
-  pub fn queue(&mut self, data: Vec<i16>) {
-    self.device.lock().queue(data);
+  fn queue(&self, data: &[i16]) {
+    // SAFETY: the device is open and SDL copies the given number of bytes out of the slice before it returns
+    unsafe { sys::SDL_QueueAudio(self.id, data.as_ptr() as *const _, mem::size_of_val(data) as u32); }
   }
 
-  pub fn queue_size(&mut self) -> usize {
-    self.device.lock().queue.len()
+  fn queued_bytes(&self) -> usize {
+    // SAFETY: the device is open
+    unsafe { sys::SDL_GetQueuedAudioSize(self.id) as usize }
   }
 
-  pub fn play(&mut self) {
-    match self.device.status() {
-      AudioStatus::Paused | AudioStatus::Stopped => self.device.resume(),
-      _ => ()
-    }
+  fn pause(&self, paused: bool) {
+    // SAFETY: the device is open
+    unsafe { sys::SDL_PauseAudioDevice(self.id, paused as i32); }
   }
+}
 
-  pub fn stop(&mut self) {
-    match self.device.status() {
-      AudioStatus::Playing => self.device.pause(),
-      _ => ()
-    }
+impl Drop for QueueDevice {
+  fn drop(&mut self) {
+    // SAFETY: the id came from a successful SDL_OpenAudioDevice and is closed only here
+    unsafe { sys::SDL_CloseAudioDevice(self.id); }
   }
-}
\ No newline at end of file
+}